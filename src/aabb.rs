@@ -0,0 +1,108 @@
+use crate::{interval, ray, vec3};
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Aabb {
+    x: interval::Interval,
+    y: interval::Interval,
+    z: interval::Interval,
+}
+
+impl Aabb {
+    pub fn new(x: interval::Interval, y: interval::Interval, z: interval::Interval) -> Self {
+        Self { x, y, z }
+    }
+
+    pub fn from_points(a: vec3::Point3, b: vec3::Point3) -> Self {
+        // Treat the two points a and b as extrema for the bounding box, so we don't require a
+        // particular minimum/maximum coordinate order.
+        Self {
+            x: interval::Interval::new(a.x().min(b.x()), a.x().max(b.x())),
+            y: interval::Interval::new(a.y().min(b.y()), a.y().max(b.y())),
+            z: interval::Interval::new(a.z().min(b.z()), a.z().max(b.z())),
+        }
+    }
+
+    pub fn padded(&self) -> Self {
+        // Adjust the AABB so that no side is narrower than some delta, so flat primitives such
+        // as axis-aligned triangles still have a box the slab test can hit.
+        let delta = 0.0001;
+        let pad = |i: interval::Interval| if i.size() < delta { i.expand(delta) } else { i };
+        Self {
+            x: pad(self.x),
+            y: pad(self.y),
+            z: pad(self.z),
+        }
+    }
+
+    pub fn surrounding(box0: Aabb, box1: Aabb) -> Self {
+        Self {
+            x: interval::Interval::enclosing(box0.x, box1.x),
+            y: interval::Interval::enclosing(box0.y, box1.y),
+            z: interval::Interval::enclosing(box0.z, box1.z),
+        }
+    }
+
+    pub fn axis_interval(&self, n: usize) -> interval::Interval {
+        match n {
+            1 => self.y,
+            2 => self.z,
+            _ => self.x,
+        }
+    }
+
+    pub fn centroid(&self, axis: usize) -> f64 {
+        let i = self.axis_interval(axis);
+        0.5 * (i.min() + i.max())
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        if self.x.size() > self.y.size() {
+            if self.x.size() > self.z.size() {
+                0
+            } else {
+                2
+            }
+        } else if self.y.size() > self.z.size() {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let (dx, dy, dz) = (self.x.size(), self.y.size(), self.z.size());
+        if dx < 0.0 || dy < 0.0 || dz < 0.0 {
+            // An empty box has no area, rather than the negative product of inverted intervals.
+            return 0.0;
+        }
+        2.0 * (dx * dy + dy * dz + dz * dx)
+    }
+
+    pub fn hit(&self, r: ray::Ray, ray_t: interval::Interval) -> bool {
+        let ray_orig = r.origin();
+        let ray_dir = r.direction();
+        let mut t_min = ray_t.min();
+        let mut t_max = ray_t.max();
+
+        for axis in 0..3 {
+            let ax = self.axis_interval(axis);
+            let adinv = 1.0 / ray_dir.axis(axis);
+
+            let t0 = (ax.min() - ray_orig.axis(axis)) * adinv;
+            let t1 = (ax.max() - ray_orig.axis(axis)) * adinv;
+
+            if t0 < t1 {
+                t_min = t_min.max(t0);
+                t_max = t_max.min(t1);
+            } else {
+                t_min = t_min.max(t1);
+                t_max = t_max.min(t0);
+            }
+
+            if t_max <= t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,174 @@
+use std::sync::Arc;
+
+use crate::{aabb, hittable, hittable_list, interval, ray};
+
+pub struct BvhNode {
+    left: Arc<dyn hittable::Hittable>,
+    right: Arc<dyn hittable::Hittable>,
+    bbox: aabb::Aabb,
+}
+
+impl BvhNode {
+    pub fn new(list: &hittable_list::HittableList) -> Self {
+        let mut objects = list.objects().to_vec();
+        Self::build(&mut objects)
+    }
+
+    fn build(objects: &mut [Arc<dyn hittable::Hittable>]) -> Self {
+        match objects.len() {
+            0 => panic!("cannot build a BVH node without any objects"),
+            1 => {
+                let obj = objects[0].clone();
+                Self::from_children(obj.clone(), obj)
+            }
+            2 => Self::from_children(objects[0].clone(), objects[1].clone()),
+            _ => {
+                let (axis, split) = sah_split(objects);
+                sort_by_centroid(objects, axis);
+                let (l, r) = objects.split_at_mut(split);
+                Self::from_children(Arc::new(Self::build(l)), Arc::new(Self::build(r)))
+            }
+        }
+    }
+
+    fn from_children(
+        left: Arc<dyn hittable::Hittable>,
+        right: Arc<dyn hittable::Hittable>,
+    ) -> Self {
+        let bbox = aabb::Aabb::surrounding(left.bounding_box(), right.bounding_box());
+        Self { left, right, bbox }
+    }
+}
+
+impl hittable::Hittable for BvhNode {
+    fn hit(&self, r: ray::Ray, ray_t: interval::Interval, rec: &mut hittable::HitRecord) -> bool {
+        if !self.bbox.hit(r, ray_t) {
+            return false;
+        }
+
+        let hit_left = self.left.hit(r, ray_t, rec);
+        let right_max = if hit_left { rec.t() } else { ray_t.max() };
+        let hit_right = self
+            .right
+            .hit(r, interval::Interval::new(ray_t.min(), right_max), rec);
+
+        hit_left || hit_right
+    }
+
+    fn bounding_box(&self) -> aabb::Aabb {
+        self.bbox
+    }
+}
+
+fn sort_by_centroid(objects: &mut [Arc<dyn hittable::Hittable>], axis: usize) {
+    objects.sort_by(|a, b| {
+        a.bounding_box()
+            .centroid(axis)
+            .total_cmp(&b.bounding_box().centroid(axis))
+    });
+}
+
+fn sah_split(objects: &mut [Arc<dyn hittable::Hittable>]) -> (usize, usize) {
+    // Surface area heuristic: for every axis, sweep the centroid-sorted objects and pick the
+    // partition minimising area(left) * count(left) + area(right) * count(right).
+    let n = objects.len();
+    let mut best: Option<(f64, usize, usize)> = None;
+
+    for axis in 0..3 {
+        sort_by_centroid(objects, axis);
+
+        let mut right_areas = vec![0.0; n];
+        let mut right_box = aabb::Aabb::default();
+        for i in (1..n).rev() {
+            right_box = aabb::Aabb::surrounding(right_box, objects[i].bounding_box());
+            right_areas[i] = right_box.surface_area();
+        }
+
+        let mut left_box = aabb::Aabb::default();
+        for i in 1..n {
+            left_box = aabb::Aabb::surrounding(left_box, objects[i - 1].bounding_box());
+            let cost = left_box.surface_area() * (i as f64) + right_areas[i] * ((n - i) as f64);
+            if cost.is_finite() && best.is_none_or(|(c, _, _)| cost < c) {
+                best = Some((cost, axis, i));
+            }
+        }
+    }
+
+    match best {
+        Some((_, axis, split)) => (axis, split),
+        None => {
+            // Unbounded children make every cost infinite; fall back to a median split.
+            let bbox = objects.iter().fold(aabb::Aabb::default(), |b, o| {
+                aabb::Aabb::surrounding(b, o.bounding_box())
+            });
+            (bbox.longest_axis(), n / 2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::{material, quad, sphere, triangle, utils, vec3};
+
+    fn random_point(rng: &mut utils::Rng, extent: f64) -> vec3::Point3 {
+        vec3::Point3::new(
+            utils::random_double_bounded(rng, -extent, extent),
+            utils::random_double_bounded(rng, -extent, extent),
+            utils::random_double_bounded(rng, -extent, extent),
+        )
+    }
+
+    #[test]
+    fn traversal_finds_the_same_closest_hit_as_a_linear_scan() {
+        // Overlapping spheres, triangles and quads of mixed sizes, so the SAH splits are uneven
+        // and many rays pass through several nodes.
+        let mut rng = utils::Rng::new(3);
+        let mat: Arc<dyn material::Material> = Arc::new(material::Lambertian::default());
+        let mut list = hittable_list::HittableList::new();
+        for i in 0..300 {
+            let p = random_point(&mut rng, 10.0);
+            let size = utils::random_double_bounded(&mut rng, 0.05, 1.5);
+            let u = size * vec3::random_unit_vector(&mut rng);
+            let v = size * vec3::random_unit_vector(&mut rng);
+            match i % 3 {
+                0 => list.add(Arc::new(sphere::Sphere::new(p, size, mat.clone()))),
+                1 => list.add(Arc::new(triangle::Triangle::new(
+                    p,
+                    p + u,
+                    p + v,
+                    mat.clone(),
+                ))),
+                _ => list.add(Arc::new(quad::Quad::new(p, u, v, mat.clone()))),
+            }
+        }
+        let bvh = BvhNode::new(&list);
+
+        let mut hits = 0;
+        for _ in 0..5000 {
+            let r = ray::Ray::new(
+                random_point(&mut rng, 15.0),
+                vec3::random_unit_vector(&mut rng),
+            );
+            let ray_t = interval::Interval::new(0.001, f64::INFINITY);
+            let mut expected = hittable::HitRecord::default();
+            let mut found = hittable::HitRecord::default();
+            let hit = list.hit(r, ray_t, &mut expected);
+            assert_eq!(bvh.hit(r, ray_t, &mut found), hit);
+            if hit {
+                hits += 1;
+                assert_eq!(found.t(), expected.t());
+                assert_eq!(
+                    [found.normal().x(), found.normal().y(), found.normal().z()],
+                    [
+                        expected.normal().x(),
+                        expected.normal().y(),
+                        expected.normal().z()
+                    ]
+                );
+            }
+        }
+        assert!(hits > 500, "only {} rays hit anything", hits);
+    }
+}
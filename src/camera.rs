@@ -1,6 +1,37 @@
-use crate::{color, hittable, interval, ray, utils, vec3};
+use std::io;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
 
-#[derive(Debug, Copy, Clone)]
+use crate::{
+    color, environment, film, hittable, hittable_list, interval, material, pdf, ray, sampler,
+    spectrum, utils, vec3,
+};
+
+// What a ray that leaves the scene sees.
+#[derive(Debug, Clone)]
+pub enum Background {
+    Sky, // White-to-blue gradient from the first book
+    Solid(color::Color),
+    Environment(Arc<environment::EnvironmentMap>),
+}
+
+impl Background {
+    pub fn value(&self, r: ray::Ray) -> color::Color {
+        match self {
+            Background::Sky => {
+                let unit_direction = vec3::unit_vector(r.direction());
+                let a = 0.5 * (unit_direction.y() + 1.0);
+                ((1.0 - a) * color::Color::new(1.0, 1.0, 1.0))
+                    + (a * color::Color::new(0.5, 0.7, 1.0))
+            }
+            Background::Solid(c) => *c,
+            Background::Environment(map) => map.value(r.direction()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Camera {
     image_width: i64,
 
@@ -10,14 +41,25 @@ pub struct Camera {
     pixel_delta_u: vec3::Vec3, // Offset to pixel to the right
     pixel_delta_v: vec3::Vec3, // Offset to pixel below
     samples_per_pixel: i64,    // Count of random samples for each pixel
-    pixel_samples_scale: f64,  // Color scale factor for a sum of pixel samples
+    pass_samples: i64,         // Samples each pixel takes per progressive pass
+    min_samples: i64,          // Samples every pixel takes before adaptive sampling may stop
+    noise_threshold: f64,      // Noise a pixel is sampled down to; 0 disables adaptive sampling
     max_depth: i64,            // max number of ray bounces into scene
+    min_depth: i64,            // Bounces before Russian roulette may end a path
     defocus_disk_u: vec3::Vec3,
     defocus_disk_v: vec3::Vec3,
     defocus_angle: f64,
+    threads: usize, // Number of worker threads render splits scanlines across
+    seed: u64,      // Seed every pixel's random stream is derived from
+    sampler: sampler::SamplerKind, // Source of pixel, lens, time and scattering samples
+    background: Background, // Scene background color
+    shutter_open: f64, // Time the shutter opens; rays sample times up to shutter_close
+    shutter_close: f64,
+    spectral: bool, // Trace paths at sampled wavelengths instead of in RGB
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         aspect_ratio: f64,
         image_width: i64,
@@ -59,8 +101,6 @@ impl Camera {
         let defocus_disk_u = u * defocus_radius;
         let defocus_disk_v = v * defocus_radius;
 
-        let pixel_samples_scale = 1.0 / (samples_per_pixel as f64);
-
         Self {
             image_width,
             image_height,
@@ -69,85 +109,365 @@ impl Camera {
             pixel_delta_v,
             pixel00_loc,
             samples_per_pixel,
-            pixel_samples_scale,
+            pass_samples: samples_per_pixel,
+            min_samples: samples_per_pixel,
+            noise_threshold: 0.0,
             max_depth,
+            min_depth: 5,
             defocus_disk_u,
             defocus_disk_v,
             defocus_angle,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            seed: utils::random_seed(),
+            sampler: sampler::SamplerKind::default(),
+            background: Background::Sky,
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            spectral: false,
         }
     }
 
-    pub fn render(&self, world: &dyn hittable::Hittable) {
-        println!("P3\n{0} {1}\n255\n", self.image_width, self.image_height);
+    pub fn image_size(&self) -> (usize, usize) {
+        (self.image_width as usize, self.image_height as usize)
+    }
+
+    pub fn set_pass_samples(&mut self, pass_samples: i64) {
+        self.pass_samples = pass_samples.max(1);
+    }
 
-        for j in 0..self.image_height {
-            eprint!("\rScanlines remaining: {}\n", (self.image_height - j));
-            for i in 0..self.image_width {
-                let mut pixel_color = color::Color::new(0.0, 0.0, 0.0);
-                for _ in 0..self.samples_per_pixel {
-                    let r = self.get_ray(i, j);
-                    pixel_color += ray_color(r, self.max_depth, world);
-                }
-                color::write_color(self.pixel_samples_scale * pixel_color);
+    pub fn set_shutter(&mut self, open: f64, close: f64) {
+        self.shutter_open = open;
+        self.shutter_close = close;
+    }
+
+    pub fn set_background(&mut self, background: Background) {
+        self.background = background;
+    }
+
+    pub fn set_spectral(&mut self, spectral: bool) {
+        self.spectral = spectral;
+    }
+
+    pub fn set_adaptive(&mut self, noise_threshold: f64, min_samples: i64) {
+        // Pixels take at least min_samples and then stop once their estimated noise falls
+        // under noise_threshold. The samples they don't use go to the noisier pixels, so the
+        // image as a whole still takes samples_per_pixel per pixel on average.
+        self.noise_threshold = noise_threshold.max(0.0);
+        self.min_samples = min_samples.clamp(1, self.samples_per_pixel);
+    }
+
+    pub fn set_min_depth(&mut self, min_depth: i64) {
+        self.min_depth = min_depth.max(0);
+    }
+
+    pub fn set_sampler(&mut self, sampler: sampler::SamplerKind) {
+        self.sampler = sampler;
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    pub fn render(
+        &self,
+        world: &dyn hittable::Hittable,
+        lights: &hittable_list::HittableList,
+        film: &mut film::Film,
+        after_pass: &mut dyn FnMut(&film::Film) -> io::Result<()>,
+    ) -> io::Result<()> {
+        // Renders progressively: every pass adds up to pass_samples samples to each pixel that
+        // still needs them and then hands the film to after_pass, until every pixel has
+        // samples_per_pixel samples, or with adaptive sampling until every pixel has converged
+        // or the image's sample budget is spent. The film may already hold samples from an
+        // earlier run, in which case rendering picks up where it left off.
+        let samples_before = film.total_samples();
+        let mut segments = 0;
+        let mut pass = 0;
+        loop {
+            let pass_samples = self.next_pass_samples(film);
+            if pass_samples == 0 {
+                break;
+            }
+            pass += 1;
+            let next_row = AtomicI64::new(0);
+            let pass_segments = AtomicU64::new(0);
+            let current: &film::Film = film;
+
+            // Workers pull whole scanlines off a shared counter so faster threads pick up the
+            // slack.
+            let rows: Vec<(usize, Vec<film::PixelState>)> = thread::scope(|s| {
+                let workers: Vec<_> = (0..self.threads)
+                    .map(|_| {
+                        s.spawn(|| {
+                            let mut done = Vec::new();
+                            loop {
+                                let j = next_row.fetch_add(1, Ordering::Relaxed);
+                                if j >= self.image_height {
+                                    break;
+                                }
+                                let mut row = current.row(j as usize).to_vec();
+                                let row_segments =
+                                    self.render_row(j, &mut row, pass_samples, world, lights);
+                                pass_segments.fetch_add(row_segments, Ordering::Relaxed);
+                                done.push((j as usize, row));
+                            }
+                            done
+                        })
+                    })
+                    .collect();
+                workers
+                    .into_iter()
+                    .flat_map(|w| w.join().expect("render thread panicked"))
+                    .collect()
+            });
+
+            for (j, row) in rows {
+                film.row_mut(j).copy_from_slice(&row);
+            }
+            segments += pass_segments.into_inner();
+            let pixels = (film.width() * film.height()) as f64;
+            eprintln!(
+                "Pass {}: {:.1} samples per pixel",
+                pass,
+                film.total_samples() as f64 / pixels
+            );
+            after_pass(film)?;
+        }
+        eprintln!("Done.");
+
+        let paths = film.total_samples() - samples_before;
+        if paths > 0 {
+            eprintln!(
+                "Average path length: {:.2} segments",
+                segments as f64 / paths as f64
+            );
+        }
+        Ok(())
+    }
+
+    fn render_row(
+        &self,
+        j: i64,
+        row: &mut [film::PixelState],
+        pass_samples: i64,
+        world: &dyn hittable::Hittable,
+        lights: &hittable_list::HittableList,
+    ) -> u64 {
+        // Adds this pass's samples to a row and returns the number of ray segments traced.
+        let mut segments = 0;
+        let mut sampler = self.sampler.build(self.samples_per_pixel, self.seed);
+        for (i, state) in (0..self.image_width).zip(row.iter_mut()) {
+            let mut taken = 0;
+            while taken < pass_samples && !self.pixel_done(state) {
+                // Every sample gets its own random stream, so the image doesn't depend on how
+                // the samples were split into passes or runs.
+                let n = state.count();
+                let stream = utils::hash(&[(j * self.image_width + i) as u64, n as u64]);
+                let mut rng = utils::Rng::for_stream(self.seed, stream);
+
+                sampler.start_pixel_sample(i, j, n);
+                let r = self.get_ray(i, j, sampler.as_mut());
+                let sample =
+                    self.ray_color(r, world, lights, sampler.as_mut(), &mut rng, &mut segments);
+                state.add_sample(sample);
+                taken += 1;
             }
         }
-        eprintln!("\rDone.");
+        segments
+    }
+
+    fn next_pass_samples(&self, film: &film::Film) -> i64 {
+        // Samples each unfinished pixel takes in the next pass, or 0 once the render is done.
+        let unfinished = (0..film.height())
+            .map(|j| film.row(j).iter().filter(|p| !self.pixel_done(p)).count())
+            .sum::<usize>() as i64;
+        if unfinished == 0 {
+            return 0;
+        }
+        if self.noise_threshold <= 0.0 {
+            return self.pass_samples;
+        }
+        // With adaptive sampling no single pixel is capped; instead the whole image gets
+        // samples_per_pixel samples per pixel, shared out among the pixels still noisy. The
+        // last pass is shortened so the budget is overshot by less than one sample per pixel.
+        let pixels = (film.width() * film.height()) as i64;
+        let remaining = self.samples_per_pixel.saturating_mul(pixels) - film.total_samples();
+        if remaining <= 0 {
+            return 0;
+        }
+        let share = (remaining + unfinished - 1) / unfinished;
+        self.pass_samples.min(share)
+    }
+
+    fn pixel_done(&self, state: &film::PixelState) -> bool {
+        let n = state.count();
+        if self.noise_threshold <= 0.0 {
+            return n >= self.samples_per_pixel;
+        }
+        if n < self.min_samples.max(2) {
+            return false;
+        }
+        // Standard error of the pixel's mean luminance, carried through the sqrt gamma curve
+        // the image is written with, so dark and bright pixels are judged by visible noise.
+        let std_error = (state.luminance_variance() / n as f64).sqrt();
+        let displayed_error = std_error / (2.0 * state.luminance_mean().max(1e-4).sqrt());
+        displayed_error <= self.noise_threshold
     }
 
-    fn get_ray(&self, i: i64, j: i64) -> ray::Ray {
+    fn get_ray(&self, i: i64, j: i64, sampler: &mut dyn sampler::Sampler) -> ray::Ray {
         // Construct a camera ray originating from the defocus disk and directed at randomly sampled
         // point around the pixel location i, j.
-        let offset = sample_square();
+        let offset = sample_square(sampler.get_2d());
         let pixel_sample = self.pixel00_loc
             + (((i as f64) + offset.x()) * self.pixel_delta_u)
             + (((j as f64) + offset.y()) * self.pixel_delta_v);
         let ray_origin = if self.defocus_angle <= 0.0 {
             self.center
         } else {
-            self.defocus_disk_sample()
+            self.defocus_disk_sample(sampler.get_2d())
         };
         let ray_direction = pixel_sample - ray_origin;
-        ray::Ray::new(ray_origin, ray_direction)
+        let u = sampler.get_1d();
+        let ray_time = self.shutter_open + u * (self.shutter_close - self.shutter_open);
+        ray::Ray::new_with_time(ray_origin, ray_direction, ray_time)
     }
 
-    fn defocus_disk_sample(&self) -> vec3::Point3 {
+    fn ray_color(
+        &self,
+        r: ray::Ray,
+        world: &dyn hittable::Hittable,
+        lights: &hittable_list::HittableList,
+        sampler: &mut dyn sampler::Sampler,
+        rng: &mut utils::Rng,
+        segments: &mut u64,
+    ) -> color::Color {
+        // Follows one path through the scene, carrying the fraction of light that still reaches
+        // the camera from the current vertex in throughput.
+        // A spectral path carries its values at each sampled wavelength in the color channels
+        // and is converted to RGB once it ends.
+        let mut color = color::Color::default();
+        let mut throughput = color::Color::new(1.0, 1.0, 1.0);
+        let mut r = r;
+        let mut wavelengths = self
+            .spectral
+            .then(|| spectrum::Wavelengths::sample(sampler.get_1d()));
+
+        // if we've exceeded the limit, no more light is gathered
+        for depth in 0..self.max_depth {
+            *segments += 1;
+            if let Some(wavelengths) = &wavelengths {
+                r.set_wavelength(wavelengths.hero());
+            }
+            // Media can't draw samples inside hit(), so the segment carries the one they use.
+            r.set_medium_sample(sampler.get_1d());
+
+            // If the ray hits nothing, add the background color.
+            let mut rec = hittable::HitRecord::default();
+            if !world.hit(r, interval::Interval::new(0.001, f64::INFINITY), &mut rec) {
+                color += throughput * upsample(wavelengths.as_ref(), self.background.value(r));
+                break;
+            }
+
+            let mat = rec.mat();
+            let (u, v) = rec.uv();
+            color += throughput * upsample(wavelengths.as_ref(), mat.emitted(u, v, rec.p()));
+
+            // Drawn on every bounce, used or not, so each bounce keeps the same sampler
+            // dimensions across a pixel's samples.
+            let scatter_sample = sampler.get_2d();
+
+            let mut srec = material::ScatterRecord::default();
+            if !mat.scatter(r, &mut rec, &mut srec, rng) {
+                break;
+            }
+            if srec.is_dispersive() {
+                if let Some(wavelengths) = wavelengths.as_mut() {
+                    wavelengths.terminate_secondary();
+                }
+            }
+
+            match srec.pdf() {
+                // Specular scattering has a single direction, so there is nothing to sample.
+                None => {
+                    throughput = throughput * upsample(wavelengths.as_ref(), srec.attenuation());
+                    r = srec.skip_pdf_ray();
+                }
+                Some(surface_pdf) => {
+                    // Mix the material's own pdf with one aimed at the lights. Either way the
+                    // estimate is divided by the density the direction was actually drawn from,
+                    // so it stays unbiased.
+                    let light_pdf = pdf::HittablePdf::new(lights, rec.p());
+                    let mixture_pdf = pdf::MixturePdf::new(&light_pdf, surface_pdf);
+                    let sampling_pdf: &dyn pdf::Pdf = if lights.objects().is_empty() {
+                        surface_pdf
+                    } else {
+                        &mixture_pdf
+                    };
+
+                    let scattered = ray::Ray::new_with_time(
+                        rec.p(),
+                        sampling_pdf.generate(scatter_sample),
+                        r.time(),
+                    );
+                    let pdf_value = sampling_pdf.value(scattered.direction());
+                    let scattering = upsample(
+                        wavelengths.as_ref(),
+                        mat.scattering(r, &rec, &srec, scattered),
+                    );
+                    if pdf_value <= 0.0
+                        || scattering.x().max(scattering.y()).max(scattering.z()) <= 0.0
+                    {
+                        break;
+                    }
+                    throughput = throughput * scattering / pdf_value;
+                    r = scattered;
+                }
+            }
+
+            // Russian roulette: past min_depth, continue with a probability that follows the
+            // throughput and boost the survivors to match, so dim paths end early without
+            // changing the expected result.
+            if depth + 1 >= self.min_depth {
+                let survival = throughput
+                    .x()
+                    .max(throughput.y())
+                    .max(throughput.z())
+                    .min(1.0);
+                if utils::random_double(rng) >= survival {
+                    break;
+                }
+                throughput = throughput / survival;
+            }
+        }
+
+        match wavelengths {
+            Some(wavelengths) => wavelengths.to_rgb(color),
+            None => color,
+        }
+    }
+
+    fn defocus_disk_sample(&self, u: (f64, f64)) -> vec3::Point3 {
         // Returns a random point in the camera defocus disk.
-        let p = vec3::random_in_unit_disk();
+        let p = vec3::sample_unit_disk(u);
         self.center + (p.x() * self.defocus_disk_u) + (p.y() * self.defocus_disk_v)
     }
 }
 
-fn sample_square() -> vec3::Vec3 {
-    // Returns the vector to a random point in the [-.5,-.5]-[+.5,+.5] unit square.
-    vec3::Vec3::new(
-        utils::random_double() - 0.5,
-        utils::random_double() - 0.5,
-        0.0,
-    )
-}
-
-fn ray_color(r: ray::Ray, depth: i64, world: &dyn hittable::Hittable) -> color::Color {
-    // if we've exceeded the limit, no more light is gathered
-    if depth <= 0 {
-        return color::Color::default();
-    }
-
-    let mut rec = hittable::HitRecord::default();
-    if world.hit(r, interval::Interval::new(0.001, f64::INFINITY), &mut rec) {
-        let mut scattered = ray::Ray::default();
-        let mut attenuation = color::Color::default();
-        if rec
-            .mat()
-            .scatter(r, &mut rec, &mut attenuation, &mut scattered)
-        {
-            return attenuation * ray_color(scattered, depth - 1, world);
-        }
-        return color::Color::default();
+fn upsample(wavelengths: Option<&spectrum::Wavelengths>, rgb: color::Color) -> color::Color {
+    // Material and light colors are RGB; a spectral path needs their values at its wavelengths.
+    match wavelengths {
+        Some(wavelengths) => wavelengths.upsample(rgb),
+        None => rgb,
     }
+}
 
-    let unit_direction = vec3::unit_vector(r.direction());
-    let a = 0.5 * (unit_direction.y() + 1.0);
-    return ((1.0 - a) * color::Color::new(1.0, 1.0, 1.0)) + (a * color::Color::new(0.5, 0.7, 1.0));
+fn sample_square((u1, u2): (f64, f64)) -> vec3::Vec3 {
+    // Returns the vector to a random point in the [-.5,-.5]-[+.5,+.5] unit square.
+    vec3::Vec3::new(u1 - 0.5, u2 - 0.5, 0.0)
 }
 
 fn calculate_image_height(image_width: i64, aspect_ratio: f64) -> i64 {
@@ -158,3 +478,119 @@ fn calculate_image_height(image_width: i64, aspect_ratio: f64) -> i64 {
         height
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    use crate::{bvh, scene, sphere};
+
+    // Renders a small diffuse and metal scene under the sky and returns its pixels.
+    fn render(seed: u64, threads: usize, sampler: sampler::SamplerKind) -> Vec<[f64; 3]> {
+        let mut world = hittable_list::HittableList::new();
+        let ground = Arc::new(material::Lambertian::new(color::Color::new(0.5, 0.5, 0.5)));
+        let metal = Arc::new(material::Metal::new(color::Color::new(0.7, 0.6, 0.5), 0.2));
+        world.add(Arc::new(sphere::Sphere::new(
+            vec3::Point3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            ground,
+        )));
+        world.add(Arc::new(sphere::Sphere::new(
+            vec3::Point3::new(0.0, 1.0, 0.0),
+            1.0,
+            metal,
+        )));
+
+        let settings = scene::CameraSettings {
+            aspect_ratio: 1.0,
+            image_width: 12,
+            samples_per_pixel: 6,
+            pass_samples: 4,
+            sampler,
+            ..scene::CameraSettings::default()
+        };
+        let mut cam = settings.build();
+        cam.set_seed(seed);
+        cam.set_threads(threads);
+
+        let (width, height) = cam.image_size();
+        let mut film = film::Film::new(width, height, seed, 0);
+        cam.render(
+            &world,
+            &hittable_list::HittableList::new(),
+            &mut film,
+            &mut |_| Ok(()),
+        )
+        .unwrap();
+        let image = film.image();
+        image
+            .pixels()
+            .iter()
+            .map(|c| [c.x(), c.y(), c.z()])
+            .collect()
+    }
+
+    #[test]
+    fn seeded_render_is_repeatable() {
+        let first = render(7, 1, sampler::SamplerKind::Independent);
+        assert_eq!(first, render(7, 1, sampler::SamplerKind::Independent));
+        assert_ne!(first, render(8, 1, sampler::SamplerKind::Independent));
+    }
+
+    #[test]
+    fn seeded_render_does_not_depend_on_thread_count() {
+        for sampler in [
+            sampler::SamplerKind::Independent,
+            sampler::SamplerKind::Sobol,
+        ] {
+            let single = render(7, 1, sampler);
+            assert_eq!(single, render(7, 3, sampler));
+            assert_eq!(single, render(7, 8, sampler));
+        }
+    }
+
+    // Mean radiance of the smoke-filled Cornell box, rendered with or without sampling its
+    // light directly.
+    fn cornell_mean(sample_lights: bool) -> [f64; 3] {
+        let mut rng = utils::Rng::new(1);
+        let src = include_str!("../scenes/smoke.toml");
+        let mut scene = scene::parse(src, Path::new("scenes"), &mut rng).unwrap();
+        scene.camera.image_width = 8;
+        scene.camera.samples_per_pixel = 2048;
+        scene.camera.pass_samples = 2048;
+        if !sample_lights {
+            scene.lights.clear();
+        }
+        let mut cam = scene.camera.build();
+        cam.set_seed(5);
+        cam.set_threads(4);
+
+        let world = bvh::BvhNode::new(&scene.world);
+        let (width, height) = cam.image_size();
+        let mut film = film::Film::new(width, height, 5, 0);
+        cam.render(&world, &scene.lights, &mut film, &mut |_| Ok(()))
+            .unwrap();
+        let image = film.image();
+        let sum = image
+            .pixels()
+            .iter()
+            .fold(color::Color::default(), |sum, &c| sum + c);
+        let mean = sum / image.pixels().len() as f64;
+        [mean.x(), mean.y(), mean.z()]
+    }
+
+    #[test]
+    fn light_sampling_matches_brute_force() {
+        let sampled = cornell_mean(true);
+        let brute_force = cornell_mean(false);
+        for (a, b) in sampled.iter().zip(brute_force) {
+            assert!(
+                (a - b).abs() < 0.03 * b,
+                "light sampling gives {:?} but brute force {:?}",
+                sampled,
+                brute_force
+            );
+        }
+    }
+}
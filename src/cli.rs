@@ -0,0 +1,239 @@
+use std::path::PathBuf;
+
+use clap::builder::RangedU64ValueParser;
+use clap::Parser;
+
+use crate::{output, sampler, scene};
+
+// Render settings left unset on the command line keep the value from the scene, so a scene file
+// can pick its own resolution and the flags only override what is given explicitly.
+#[derive(Parser, Debug)]
+#[command(version, about = "Ray Tracing in One Weekend, in Rust")]
+pub struct Cli {
+    #[arg(short, long, default_value = "final", help = scene_help())]
+    pub scene: String,
+
+    /// Output image path; the format follows the extension. Without it, P3 PPM goes to stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Output format (p3, p6, png, pfm), overriding the output file extension
+    #[arg(short, long)]
+    pub format: Option<output::Format>,
+
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(i64).range(1..),
+        help = default_help("Rendered image width in pixels", defaults().image_width)
+    )]
+    pub width: Option<i64>,
+
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(i64).range(1..),
+        help = default_help("Random samples per pixel", defaults().samples_per_pixel)
+    )]
+    pub samples: Option<i64>,
+
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(i64).range(1..),
+        help = default_help("Samples each pixel takes per progressive pass", defaults().pass_samples)
+    )]
+    pub pass_samples: Option<i64>,
+
+    /// Save the accumulated samples to this file periodically and when the render finishes
+    #[arg(long)]
+    pub checkpoint: Option<PathBuf>,
+
+    /// Seconds between checkpoints
+    #[arg(long, default_value_t = 60)]
+    pub checkpoint_interval: u64,
+
+    /// Continue the render saved in --checkpoint, adding samples up to --samples
+    #[arg(long, requires = "checkpoint")]
+    pub resume: bool,
+
+    /// Rewrite this image with the render so far while it runs; the format follows the extension
+    #[arg(long)]
+    pub preview: Option<PathBuf>,
+
+    /// Seconds between preview updates
+    #[arg(long, default_value_t = 5)]
+    pub preview_interval: u64,
+
+    /// Update the preview every this many passes instead of on a timer
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub preview_passes: Option<u64>,
+
+    /// Show the render converging in a window
+    #[cfg(feature = "window")]
+    #[arg(long)]
+    pub window: bool,
+
+    /// Sample pattern for pixel, lens, time and scattering dimensions (independent,
+    /// stratified, halton, sobol, blue_noise) [default: independent]
+    #[arg(long)]
+    pub sampler: Option<sampler::SamplerKind>,
+
+    /// Stop sampling a pixel once its estimated noise, in output brightness, falls below this
+    /// (e.g. 0.01); --samples becomes the average spent over the image [default: off]
+    #[arg(long)]
+    pub noise_threshold: Option<f64>,
+
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(i64).range(1..),
+        help = default_help("Samples per pixel before adaptive sampling may stop", defaults().min_samples)
+    )]
+    pub min_samples: Option<i64>,
+
+    /// Also write an image of how many samples each pixel took
+    #[arg(long)]
+    pub heatmap: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(i64).range(1..),
+        help = default_help("Maximum ray bounces into the scene", defaults().max_depth)
+    )]
+    pub max_depth: Option<i64>,
+
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(i64).range(0..),
+        help = default_help("Bounces before Russian roulette may end a path", defaults().min_depth)
+    )]
+    pub min_depth: Option<i64>,
+
+    #[arg(long, help = default_help("Vertical field of view in degrees", defaults().vfov))]
+    pub vfov: Option<f64>,
+
+    #[arg(
+        long,
+        help = default_help("Variation angle of rays through each pixel", defaults().defocus_angle)
+    )]
+    pub defocus_angle: Option<f64>,
+
+    #[arg(
+        long,
+        help = default_help("Distance from the camera to the plane of focus", defaults().focus_dist)
+    )]
+    pub focus_dist: Option<f64>,
+
+    /// Seed for scene generation and sampling; fixing it makes renders bit-reproducible
+    /// [default: random]
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Don't sample lights directly, only the materials' own scattering. Much noisier, but a
+    /// useful reference: both converge to the same image
+    #[arg(long)]
+    pub brute_force: bool,
+
+    /// Trace paths at sampled wavelengths, so dispersive glass splits light into colors
+    #[arg(long)]
+    pub spectral: bool,
+
+    /// Number of render threads [default: all cores]
+    #[arg(short = 'j', long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub threads: Option<usize>,
+}
+
+impl Cli {
+    pub fn apply(&self, settings: &mut scene::CameraSettings) -> Result<(), String> {
+        // clap only range-checks integers, so the floating-point settings are checked here.
+        check_flag(
+            "noise-threshold",
+            self.noise_threshold,
+            |x| x >= 0.0,
+            "must not be negative",
+        )?;
+        check_flag(
+            "vfov",
+            self.vfov,
+            |x| x > 0.0 && x < 180.0,
+            "must be between 0 and 180",
+        )?;
+        check_flag(
+            "focus-dist",
+            self.focus_dist,
+            |x| x > 0.0,
+            "must be positive",
+        )?;
+        check_flag(
+            "defocus-angle",
+            self.defocus_angle,
+            |x| (0.0..180.0).contains(&x),
+            "must be at least 0 and less than 180",
+        )?;
+
+        if let Some(width) = self.width {
+            settings.image_width = width;
+        }
+        if let Some(samples) = self.samples {
+            settings.samples_per_pixel = samples;
+        }
+        if let Some(pass_samples) = self.pass_samples {
+            settings.pass_samples = pass_samples;
+        }
+        if let Some(sampler) = self.sampler {
+            settings.sampler = sampler;
+        }
+        if let Some(noise_threshold) = self.noise_threshold {
+            settings.noise_threshold = noise_threshold;
+        }
+        if let Some(min_samples) = self.min_samples {
+            settings.min_samples = min_samples;
+        }
+        if let Some(max_depth) = self.max_depth {
+            settings.max_depth = max_depth;
+        }
+        if let Some(min_depth) = self.min_depth {
+            settings.min_depth = min_depth;
+        }
+        if let Some(vfov) = self.vfov {
+            settings.vfov = vfov;
+        }
+        if let Some(defocus_angle) = self.defocus_angle {
+            settings.defocus_angle = defocus_angle;
+        }
+        if let Some(focus_dist) = self.focus_dist {
+            settings.focus_dist = focus_dist;
+        }
+        if self.spectral {
+            settings.spectral = true;
+        }
+        Ok(())
+    }
+}
+
+fn defaults() -> scene::CameraSettings {
+    scene::CameraSettings::default()
+}
+
+fn check_flag(
+    flag: &str,
+    value: Option<f64>,
+    valid: fn(f64) -> bool,
+    expected: &str,
+) -> Result<(), String> {
+    match value {
+        Some(value) if !valid(value) => Err(format!(
+            "invalid value '{}' for '--{}': {}",
+            value, flag, expected
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn default_help(text: &str, default: impl std::fmt::Display) -> String {
+    format!("{} [default: {}]", text, default)
+}
+
+fn scene_help() -> String {
+    format!(
+        "Built-in scene name ({}) or path to a TOML scene file",
+        scene::BUILTIN_SCENES.join(", ")
+    )
+}
@@ -1,19 +1,37 @@
+use std::io::{self, Write};
+
 use crate::{interval, vec3};
 
 pub type Color = vec3::Vec3;
 
-pub fn write_color(pixel_color: Color) {
+pub fn write_color(out: &mut impl Write, pixel_color: Color) -> io::Result<()> {
+    let [rbyte, gbyte, bbyte] = to_bytes(pixel_color);
+    writeln!(out, "{0} {1} {2}", rbyte, gbyte, bbyte)
+}
+
+pub fn to_bytes(pixel_color: Color) -> [u8; 3] {
     let r = linear_to_gamma(pixel_color.x());
     let g = linear_to_gamma(pixel_color.y());
     let b = linear_to_gamma(pixel_color.z());
 
     // Translates the [0,1] component values to byte range [0,255]
     let intensity = interval::Interval::new(0.000, 0.999);
-    let rbyte: i64 = (256.0 * intensity.clamp(r)) as i64;
-    let gbyte: i64 = (256.0 * intensity.clamp(g)) as i64;
-    let bbyte: i64 = (256.0 * intensity.clamp(b)) as i64;
+    let rbyte = (256.0 * intensity.clamp(r)) as u8;
+    let gbyte = (256.0 * intensity.clamp(g)) as u8;
+    let bbyte = (256.0 * intensity.clamp(b)) as u8;
+
+    [rbyte, gbyte, bbyte]
+}
 
-    println!("{0} {1} {2}", rbyte, gbyte, bbyte);
+pub fn to_srgb_bytes(pixel_color: Color) -> [u8; 3] {
+    // 8-bit sRGB, for formats that declare it.
+    [pixel_color.x(), pixel_color.y(), pixel_color.z()]
+        .map(|c| (255.0 * linear_to_srgb(c)).round() as u8)
+}
+
+pub fn luminance(c: Color) -> f64 {
+    // Rec. 709 weights for linear RGB.
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
 }
 
 fn linear_to_gamma(linear_component: f64) -> f64 {
@@ -22,3 +40,42 @@ fn linear_to_gamma(linear_component: f64) -> f64 {
     }
     0.0
 }
+
+fn linear_to_srgb(linear_component: f64) -> f64 {
+    // The sRGB transfer function, clamped to [0,1]; the inverse of srgb_to_linear.
+    let c = linear_component.clamp(0.0, 1.0);
+    if c <= 0.0031308 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+pub fn srgb_to_linear(byte: u8) -> f64 {
+    let c = (byte as f64) / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_encodes_middle_grey() {
+        // 18% grey is 118 in 8-bit sRGB.
+        assert_eq!(to_srgb_bytes(Color::new(0.18, 0.18, 0.18)), [118, 118, 118]);
+        assert_eq!(to_srgb_bytes(Color::new(0.0, 1.0, 2.0)), [0, 255, 255]);
+    }
+
+    #[test]
+    fn srgb_encoding_inverts_decoding() {
+        for byte in 0..=255 {
+            let c = srgb_to_linear(byte);
+            assert_eq!(to_srgb_bytes(Color::new(c, c, c)), [byte; 3]);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use crate::{aabb, hittable, interval, material, ray, utils, vec3};
+
+// A volume of uniform density filling a closed boundary, such as smoke or fog. A ray passing
+// through scatters after an exponentially distributed distance, or leaves without touching it.
+pub struct ConstantMedium {
+    boundary: Arc<dyn hittable::Hittable>,
+    neg_inv_density: f64,
+    phase_function: Arc<dyn material::Material>,
+    id: u64, // Keeps this medium's free-flight samples apart from other media on the same ray
+}
+
+impl ConstantMedium {
+    pub fn new(
+        boundary: Arc<dyn hittable::Hittable>,
+        density: f64,
+        phase_function: Arc<dyn material::Material>,
+    ) -> Self {
+        let bbox = boundary.bounding_box();
+        let mut bounds = vec![density.to_bits()];
+        for axis in 0..3 {
+            let interval = bbox.axis_interval(axis);
+            bounds.extend([interval.min().to_bits(), interval.max().to_bits()]);
+        }
+        Self {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function,
+            id: utils::hash(&bounds),
+        }
+    }
+
+    fn free_flight_sample(&self, r: ray::Ray) -> f64 {
+        // The ray's sample hashed with this medium's id. Using it as is would make every medium
+        // the ray crosses scatter or let it through together, so the transmittance through two
+        // of them would come out as the smaller one's instead of their product.
+        let h = utils::hash(&[r.medium_sample().to_bits(), self.id]);
+        (h >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl hittable::Hittable for ConstantMedium {
+    fn hit(&self, r: ray::Ray, ray_t: interval::Interval, rec: &mut hittable::HitRecord) -> bool {
+        // Find where the ray enters and leaves the boundary, which may be behind its origin when
+        // the ray starts inside.
+        let mut rec1 = hittable::HitRecord::default();
+        let mut rec2 = hittable::HitRecord::default();
+
+        if !self.boundary.hit(r, interval::UNIVERSE, &mut rec1) {
+            return false;
+        }
+
+        if !self.boundary.hit(
+            r,
+            interval::Interval::new(rec1.t() + 0.0001, f64::INFINITY),
+            &mut rec2,
+        ) {
+            return false;
+        }
+
+        let t_enter = rec1.t().max(ray_t.min()).max(0.0);
+        let t_exit = rec2.t().min(ray_t.max());
+        if t_enter >= t_exit {
+            return false;
+        }
+
+        let ray_length = r.direction().length();
+        let distance_inside_boundary = (t_exit - t_enter) * ray_length;
+        // The free-flight distance. A heterogeneous medium would instead take steps like this
+        // one against a majorant density and accept each with probability density / majorant
+        // (delta tracking).
+        let hit_distance = self.neg_inv_density * (1.0 - self.free_flight_sample(r)).ln();
+
+        if hit_distance > distance_inside_boundary {
+            return false;
+        }
+
+        rec.set_t(t_enter + hit_distance / ray_length);
+        rec.set_p(r.at(rec.t()));
+        // A scattering point inside the volume has no surface, so the normal is arbitrary.
+        rec.set_face_normal(r, vec3::Vec3::new(1.0, 0.0, 0.0));
+        rec.set_mat(self.phase_function.clone());
+
+        true
+    }
+
+    fn bounding_box(&self) -> aabb::Aabb {
+        self.boundary.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::{color, hittable_list, quad, texture};
+
+    // A slab of medium between x0 and x1 that reaches far enough in y and z to catch every test
+    // ray.
+    fn slab(x0: f64, x1: f64, density: f64) -> Arc<dyn hittable::Hittable> {
+        let white = Arc::new(texture::SolidColor::new(color::Color::new(1.0, 1.0, 1.0)));
+        let phase = Arc::new(material::Isotropic::from_texture(white));
+        let boundary = quad::new_box(
+            vec3::Point3::new(x0, -10.0, -10.0),
+            vec3::Point3::new(x1, 10.0, 10.0),
+            phase.clone(),
+        );
+        Arc::new(ConstantMedium::new(Arc::new(boundary), density, phase))
+    }
+
+    #[test]
+    fn stacked_media_transmit_the_product_of_their_transmittances() {
+        let mut world = hittable_list::HittableList::new();
+        world.add(slab(0.0, 1.0, 0.5));
+        world.add(slab(2.0, 3.0, 1.0));
+
+        let n = 20_000;
+        let escaped = (0..n)
+            .filter(|&k| {
+                let mut r = ray::Ray::new(
+                    vec3::Point3::new(-1.0, 0.0, 0.0),
+                    vec3::Vec3::new(1.0, 0.0, 0.0),
+                );
+                r.set_medium_sample((k as f64 + 0.5) / n as f64);
+                let mut rec = hittable::HitRecord::default();
+                !world.hit(r, interval::Interval::new(0.001, f64::INFINITY), &mut rec)
+            })
+            .count();
+
+        let transmittance = escaped as f64 / n as f64;
+        let expected = (-0.5f64).exp() * (-1.0f64).exp();
+        assert!(
+            (transmittance - expected).abs() < 0.01,
+            "transmittance {} but expected {}",
+            transmittance,
+            expected
+        );
+    }
+}
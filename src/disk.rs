@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use crate::{aabb, hittable, interval, material, onb, ray, utils, vec3};
+
+// A flat circle. Hits get polar (u, v) coordinates: u is the angle around the normal as a
+// fraction of a turn and v the distance from the center as a fraction of the radius.
+pub struct Disk {
+    center: vec3::Point3,
+    radius: f64,
+    frame: onb::Onb, // w is the normal; u and v span the disk
+    mat: Arc<dyn material::Material>,
+    bbox: aabb::Aabb,
+}
+
+impl Disk {
+    pub fn new(
+        center: vec3::Point3,
+        normal: vec3::Vec3,
+        radius: f64,
+        mat: Arc<dyn material::Material>,
+    ) -> Self {
+        let radius = radius.max(0.0);
+        let frame = onb::Onb::new(normal);
+        // The disk reaches radius * sqrt(1 - n_axis^2) along each axis.
+        let n = frame.w();
+        let extent = |c: f64| radius * (1.0 - c * c).max(0.0).sqrt();
+        let half = vec3::Vec3::new(extent(n.x()), extent(n.y()), extent(n.z()));
+        Self {
+            center,
+            radius,
+            frame,
+            mat,
+            bbox: aabb::Aabb::from_points(center - half, center + half).padded(),
+        }
+    }
+
+    fn area(&self) -> f64 {
+        utils::PI * self.radius * self.radius
+    }
+}
+
+impl hittable::Hittable for Disk {
+    fn hit(&self, r: ray::Ray, ray_t: interval::Interval, rec: &mut hittable::HitRecord) -> bool {
+        let normal = self.frame.w();
+        let denom = vec3::dot(normal, r.direction());
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+
+        let t = vec3::dot(normal, self.center - r.origin()) / denom;
+        if !ray_t.surrounds(t) {
+            return false;
+        }
+
+        let p = r.at(t);
+        let offset = p - self.center;
+        let dist_squared = offset.length_squared();
+        if dist_squared > self.radius * self.radius {
+            return false;
+        }
+
+        let (x, y) = (
+            vec3::dot(offset, self.frame.transform(vec3::Vec3::new(1.0, 0.0, 0.0))),
+            vec3::dot(offset, self.frame.transform(vec3::Vec3::new(0.0, 1.0, 0.0))),
+        );
+        let phi = y.atan2(x) + utils::PI;
+        rec.set_t(t);
+        rec.set_p(p);
+        rec.set_uv(phi / (2.0 * utils::PI), dist_squared.sqrt() / self.radius);
+        rec.set_mat(self.mat.clone());
+        rec.set_face_normal(r, normal);
+
+        true
+    }
+
+    fn bounding_box(&self) -> aabb::Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: vec3::Point3, direction: vec3::Vec3) -> f64 {
+        // random() is uniform over the disk's area, converted to solid angle as for a quad.
+        let mut rec = hittable::HitRecord::default();
+        let r = ray::Ray::new(origin, direction);
+        if !self.hit(r, interval::Interval::new(0.001, f64::INFINITY), &mut rec) {
+            return 0.0;
+        }
+
+        let distance_squared = rec.t() * rec.t() * direction.length_squared();
+        let cosine = (vec3::dot(direction, rec.normal()) / direction.length()).abs();
+        if cosine < 1e-8 {
+            return 0.0;
+        }
+        distance_squared / (cosine * self.area())
+    }
+
+    fn random(&self, origin: vec3::Point3, u: (f64, f64)) -> vec3::Vec3 {
+        let d = vec3::sample_unit_disk(u);
+        let p = self.center + self.radius * self.frame.transform(d);
+        p - origin
+    }
+}
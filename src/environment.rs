@@ -0,0 +1,161 @@
+use crate::{aabb, color, framebuffer, hittable, interval, ray, utils, vec3};
+
+// An equirectangular HDR image surrounding the scene at infinite distance. Rays that leave the
+// scene look it up through camera::Background, and it sits in the lights list so paths can aim
+// at its bright parts; nothing ever hits it.
+#[derive(Debug)]
+pub struct EnvironmentMap {
+    image: framebuffer::Framebuffer,
+    rotation: f64,  // Radians the map is turned about the y axis
+    intensity: f64, // Factor every pixel's radiance is scaled by
+    rows: Distribution,
+    columns: Vec<Distribution>, // Distribution of each row's pixels
+}
+
+impl EnvironmentMap {
+    pub fn new(image: framebuffer::Framebuffer, rotation: f64, intensity: f64) -> Self {
+        // Pixels are picked in proportion to their luminance times sin(theta), the solid angle
+        // they cover shrinking towards the poles, by first choosing a row and then a pixel in it.
+        let (width, height) = (image.width(), image.height());
+        let columns: Vec<Distribution> = (0..height)
+            .map(|y| {
+                let sin_theta = (utils::PI * (y as f64 + 0.5) / height as f64).sin();
+                Distribution::new(
+                    (0..width)
+                        .map(|x| color::luminance(image.pixel(x, y)).max(0.0) * sin_theta)
+                        .collect(),
+                )
+            })
+            .collect();
+        let rows = Distribution::new(columns.iter().map(|c| c.total).collect());
+
+        Self {
+            image,
+            rotation: utils::degrees_to_radians(rotation),
+            intensity,
+            rows,
+            columns,
+        }
+    }
+
+    pub fn value(&self, direction: vec3::Vec3) -> color::Color {
+        // Nearest pixel rather than a filtered lookup, so the radiance is nonzero only where
+        // the sampling density is.
+        let (u, v) = self.direction_to_uv(direction);
+        let (x, y) = self.pixel_at(u, v);
+        self.intensity * self.image.pixel(x, y)
+    }
+
+    fn direction_to_uv(&self, direction: vec3::Vec3) -> (f64, f64) {
+        // u runs around the y axis as on a textured sphere; v runs from the top row at +y down
+        // to -y, the way the image is stored.
+        let d = vec3::unit_vector(rotate_y(direction, -self.rotation));
+        let phi = (-d.z()).atan2(d.x()) + utils::PI;
+        let theta = d.y().clamp(-1.0, 1.0).acos();
+        (phi / (2.0 * utils::PI), theta / utils::PI)
+    }
+
+    fn uv_to_direction(&self, u: f64, v: f64) -> vec3::Vec3 {
+        let phi = 2.0 * utils::PI * u;
+        let theta = utils::PI * v;
+        let d = vec3::Vec3::new(
+            -phi.cos() * theta.sin(),
+            theta.cos(),
+            phi.sin() * theta.sin(),
+        );
+        rotate_y(d, self.rotation)
+    }
+
+    fn pixel_at(&self, u: f64, v: f64) -> (usize, usize) {
+        let (width, height) = (self.image.width(), self.image.height());
+        let x = ((u * width as f64) as usize).min(width - 1);
+        let y = ((v * height as f64) as usize).min(height - 1);
+        (x, y)
+    }
+}
+
+impl hittable::Hittable for EnvironmentMap {
+    fn hit(
+        &self,
+        _r: ray::Ray,
+        _ray_t: interval::Interval,
+        _rec: &mut hittable::HitRecord,
+    ) -> bool {
+        false
+    }
+
+    fn bounding_box(&self) -> aabb::Aabb {
+        aabb::Aabb::default()
+    }
+
+    fn pdf_value(&self, _origin: vec3::Point3, direction: vec3::Vec3) -> f64 {
+        // The density over the image's unit square, converted to solid angle: the square maps
+        // onto the sphere with area element 2 pi^2 sin(theta).
+        let (u, v) = self.direction_to_uv(direction);
+        let sin_theta = (utils::PI * v).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        let (x, y) = self.pixel_at(u, v);
+        let density = self.rows.pdf(y) * self.columns[y].pdf(x);
+        density / (2.0 * utils::PI * utils::PI * sin_theta)
+    }
+
+    fn random(&self, _origin: vec3::Point3, (u1, u2): (f64, f64)) -> vec3::Vec3 {
+        let (v, y) = self.rows.sample(u1);
+        let (u, _) = self.columns[y].sample(u2);
+        self.uv_to_direction(u, v)
+    }
+}
+
+fn rotate_y(v: vec3::Vec3, angle: f64) -> vec3::Vec3 {
+    let (sin, cos) = angle.sin_cos();
+    vec3::Vec3::new(cos * v.x() + sin * v.z(), v.y(), -sin * v.x() + cos * v.z())
+}
+
+// Piecewise-constant density over [0, 1) with one step per weight.
+#[derive(Debug)]
+struct Distribution {
+    cdf: Vec<f64>,
+    total: f64, // Sum of the weights
+}
+
+impl Distribution {
+    fn new(weights: Vec<f64>) -> Self {
+        // All-zero weights, such as a black row, fall back to an even spread so the density
+        // stays well defined.
+        let total: f64 = weights.iter().sum();
+        let n = weights.len();
+        let mut cdf = Vec::with_capacity(n + 1);
+        let mut sum = 0.0;
+        cdf.push(0.0);
+        for w in &weights {
+            sum += if total > 0.0 {
+                w / total
+            } else {
+                1.0 / n as f64
+            };
+            cdf.push(sum);
+        }
+        cdf[n] = 1.0;
+        Self { cdf, total }
+    }
+
+    fn pdf(&self, i: usize) -> f64 {
+        let n = self.cdf.len() - 1;
+        (self.cdf[i + 1] - self.cdf[i]) * n as f64
+    }
+
+    fn sample(&self, u: f64) -> (f64, usize) {
+        // Returns the sampled point and the step it falls in.
+        let n = self.cdf.len() - 1;
+        let i = (self.cdf.partition_point(|&c| c <= u).max(1) - 1).min(n - 1);
+        let width = self.cdf[i + 1] - self.cdf[i];
+        let offset = if width > 0.0 {
+            (u - self.cdf[i]) / width
+        } else {
+            0.0
+        };
+        ((i as f64 + offset) / n as f64, i)
+    }
+}
@@ -0,0 +1,312 @@
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::{color, framebuffer};
+
+// Everything a pixel has accumulated so far: the sum of its samples and a running mean and sum
+// of squared deviations of their luminance (Welford), for adaptive sampling.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct PixelState {
+    sum: color::Color,
+    count: i64,
+    mean: f64,
+    m2: f64,
+}
+
+impl PixelState {
+    pub fn add_sample(&mut self, sample: color::Color) {
+        self.sum += sample;
+        self.count += 1;
+        let lum = color::luminance(sample);
+        let delta = lum - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (lum - self.mean);
+    }
+
+    pub fn count(&self) -> i64 {
+        self.count
+    }
+
+    pub fn luminance_mean(&self) -> f64 {
+        self.mean
+    }
+
+    pub fn luminance_variance(&self) -> f64 {
+        // Sample variance; zero until there are two samples to compare.
+        if self.count < 2 {
+            return 0.0;
+        }
+        self.m2 / (self.count - 1) as f64
+    }
+
+    fn color(&self) -> color::Color {
+        if self.count == 0 {
+            return color::Color::default();
+        }
+        self.sum / self.count as f64
+    }
+}
+
+// The float buffer a progressive render accumulates samples into. It can be checkpointed to
+// disk and loaded again to add more samples to the same image later.
+pub struct Film {
+    width: usize,
+    height: usize,
+    seed: u64, // Render seed; resuming needs it to rebuild the same scene and sample streams
+    settings: u64, // Hash of the scene and render settings the samples were taken with
+    pixels: Vec<PixelState>,
+}
+
+// Checkpoint files start with this, followed by the width, height, seed and settings hash as
+// little-endian u64s and then each pixel's state.
+const CHECKPOINT_MAGIC: &[u8; 8] = b"RTFILM02";
+const CHECKPOINT_HEADER: u64 = 40;
+const CHECKPOINT_PIXEL: u64 = 48;
+
+impl Film {
+    pub fn new(width: usize, height: usize, seed: u64, settings: u64) -> Self {
+        Self {
+            width,
+            height,
+            seed,
+            settings,
+            pixels: vec![PixelState::default(); width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn check_resumable(
+        &self,
+        width: usize,
+        height: usize,
+        settings: u64,
+    ) -> Result<(), String> {
+        // Samples can only be added to a checkpoint of the same image.
+        if (self.width, self.height) != (width, height) {
+            return Err(format!(
+                "the checkpoint is {}x{} but the camera renders {}x{}",
+                self.width, self.height, width, height
+            ));
+        }
+        if self.settings != settings {
+            return Err(String::from(
+                "the checkpoint was rendered from a different scene or with different settings",
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn row(&self, y: usize) -> &[PixelState] {
+        &self.pixels[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> &mut [PixelState] {
+        &mut self.pixels[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn total_samples(&self) -> i64 {
+        self.pixels.iter().map(|p| p.count).sum()
+    }
+
+    pub fn image(&self) -> framebuffer::Framebuffer {
+        let mut image = framebuffer::Framebuffer::new(self.width, self.height);
+        for y in 0..self.height {
+            for (pixel, state) in image.row_mut(y).iter_mut().zip(self.row(y)) {
+                *pixel = state.color();
+            }
+        }
+        image
+    }
+
+    pub fn samples_heatmap(&self) -> framebuffer::Framebuffer {
+        // Black for the fewest samples through red and yellow to white for the most.
+        let mut heatmap = framebuffer::Framebuffer::new(self.width, self.height);
+        let max = self
+            .pixels
+            .iter()
+            .map(|p| p.count)
+            .max()
+            .unwrap_or(1)
+            .max(1) as f64;
+        for y in 0..self.height {
+            for (pixel, state) in heatmap.row_mut(y).iter_mut().zip(self.row(y)) {
+                let t = state.count as f64 / max;
+                let ramp = |offset: f64| (3.0 * t - offset).clamp(0.0, 1.0);
+                // Squared because images are written gamma encoded, so the ramp shows linearly.
+                let c = color::Color::new(ramp(0.0), ramp(1.0), ramp(2.0));
+                *pixel = c * c;
+            }
+        }
+        heatmap
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        // Written next to the target and renamed over it, so a crash mid-write never leaves a
+        // truncated checkpoint behind.
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        {
+            let mut out = BufWriter::new(fs::File::create(&tmp)?);
+            out.write_all(CHECKPOINT_MAGIC)?;
+            for v in [
+                self.width as u64,
+                self.height as u64,
+                self.seed,
+                self.settings,
+            ] {
+                out.write_all(&v.to_le_bytes())?;
+            }
+            for p in &self.pixels {
+                for v in [p.sum.x(), p.sum.y(), p.sum.z()] {
+                    out.write_all(&v.to_le_bytes())?;
+                }
+                out.write_all(&p.count.to_le_bytes())?;
+                out.write_all(&p.mean.to_le_bytes())?;
+                out.write_all(&p.m2.to_le_bytes())?;
+            }
+            out.flush()?;
+        }
+        fs::rename(&tmp, path)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut input = BufReader::new(file);
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != CHECKPOINT_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not a render checkpoint", path.display()),
+            ));
+        }
+
+        let mut word = [0; 8];
+        let mut next = |input: &mut BufReader<fs::File>| -> io::Result<[u8; 8]> {
+            input.read_exact(&mut word)?;
+            Ok(word)
+        };
+        let width = u64::from_le_bytes(next(&mut input)?) as usize;
+        let height = u64::from_le_bytes(next(&mut input)?) as usize;
+        let seed = u64::from_le_bytes(next(&mut input)?);
+        let settings = u64::from_le_bytes(next(&mut input)?);
+
+        // The size is checked against the file before anything is allocated, so a corrupt
+        // header can't ask for an enormous buffer.
+        let expected_len = (width as u64)
+            .checked_mul(height as u64)
+            .and_then(|pixels| pixels.checked_mul(CHECKPOINT_PIXEL))
+            .and_then(|payload| payload.checked_add(CHECKPOINT_HEADER));
+        if expected_len != Some(file_len) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} is truncated or corrupt: a {}x{} render doesn't fit in {} bytes",
+                    path.display(),
+                    width,
+                    height,
+                    file_len
+                ),
+            ));
+        }
+
+        let mut film = Film::new(width, height, seed, settings);
+        for p in film.pixels.iter_mut() {
+            let x = f64::from_le_bytes(next(&mut input)?);
+            let y = f64::from_le_bytes(next(&mut input)?);
+            let z = f64::from_le_bytes(next(&mut input)?);
+            p.sum = color::Color::new(x, y, z);
+            p.count = i64::from_le_bytes(next(&mut input)?);
+            p.mean = f64::from_le_bytes(next(&mut input)?);
+            p.m2 = f64::from_le_bytes(next(&mut input)?);
+        }
+        Ok(film)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene;
+
+    fn checkpoint_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("film-test-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn checkpoint_round_trips() {
+        let mut film = Film::new(3, 2, 42, 7);
+        film.row_mut(0)[1].add_sample(color::Color::new(0.5, 1.0, 2.0));
+        film.row_mut(1)[2].add_sample(color::Color::new(1.0, 0.0, 0.0));
+        film.row_mut(1)[2].add_sample(color::Color::new(0.0, 0.0, 1.0));
+
+        let path = checkpoint_path("round-trip");
+        film.save(&path).unwrap();
+        let loaded = Film::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!((loaded.width(), loaded.height(), loaded.seed()), (3, 2, 42));
+        assert!(loaded.check_resumable(3, 2, 7).is_ok());
+        for y in 0..2 {
+            for (a, b) in film.row(y).iter().zip(loaded.row(y)) {
+                assert_eq!(a.count(), b.count());
+                assert_eq!(a.luminance_mean(), b.luminance_mean());
+                assert_eq!(a.luminance_variance(), b.luminance_variance());
+                let (ca, cb) = (a.color(), b.color());
+                assert_eq!([ca.x(), ca.y(), ca.z()], [cb.x(), cb.y(), cb.z()]);
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_checkpoint_is_rejected() {
+        let path = checkpoint_path("truncated");
+        Film::new(4, 4, 1, 0).save(&path).unwrap();
+        let data = fs::read(&path).unwrap();
+        fs::write(&path, &data[..data.len() - 1]).unwrap();
+        let result = Film::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(result.err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn resume_is_rejected_after_settings_change() {
+        let settings = scene::CameraSettings::default();
+        let film = Film::new(4, 4, 1, settings.fingerprint());
+        assert!(film.check_resumable(4, 4, settings.fingerprint()).is_ok());
+        assert!(film.check_resumable(4, 5, settings.fingerprint()).is_err());
+
+        let deeper = scene::CameraSettings {
+            max_depth: settings.max_depth + 1,
+            ..settings.clone()
+        };
+        assert!(film.check_resumable(4, 4, deeper.fingerprint()).is_err());
+        let spectral = scene::CameraSettings {
+            spectral: true,
+            ..settings.clone()
+        };
+        assert!(film.check_resumable(4, 4, spectral.fingerprint()).is_err());
+
+        // More samples may be added to the same image.
+        let more_samples = scene::CameraSettings {
+            samples_per_pixel: settings.samples_per_pixel * 2,
+            ..settings
+        };
+        assert!(film
+            .check_resumable(4, 4, more_samples.fingerprint())
+            .is_ok());
+    }
+}
@@ -0,0 +1,85 @@
+use crate::color;
+
+// A rendered image held in memory as linear RGB, one color per pixel in row-major order.
+#[derive(Debug, Clone)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<color::Color>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![color::Color::default(); width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> color::Color {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> &mut [color::Color] {
+        &mut self.pixels[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn pixels(&self) -> &[color::Color] {
+        &self.pixels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(c: color::Color) -> [f64; 3] {
+        [c.x(), c.y(), c.z()]
+    }
+
+    #[test]
+    fn new_image_is_black() {
+        let image = Framebuffer::new(3, 2);
+        assert_eq!((image.width(), image.height()), (3, 2));
+        assert_eq!(image.pixels().len(), 6);
+        assert!(image.pixels().iter().all(|&c| rgb(c) == [0.0; 3]));
+    }
+
+    #[test]
+    fn pixels_are_set_through_their_row() {
+        let mut image = Framebuffer::new(3, 2);
+        image.row_mut(1)[2] = color::Color::new(0.1, 0.2, 0.3);
+        assert_eq!(rgb(image.pixel(2, 1)), [0.1, 0.2, 0.3]);
+        assert_eq!(rgb(image.pixel(1, 1)), [0.0; 3]);
+        assert_eq!(rgb(image.pixel(2, 0)), [0.0; 3]);
+    }
+
+    #[test]
+    fn pixels_are_stored_top_row_first() {
+        let mut image = Framebuffer::new(2, 2);
+        for y in 0..2 {
+            for (x, pixel) in image.row_mut(y).iter_mut().enumerate() {
+                *pixel = color::Color::new(x as f64, y as f64, 0.0);
+            }
+        }
+        let order: Vec<[f64; 3]> = image.pixels().iter().map(|&c| rgb(c)).collect();
+        assert_eq!(
+            order,
+            [
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [1.0, 1.0, 0.0]
+            ]
+        );
+    }
+}
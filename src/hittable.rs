@@ -1,13 +1,16 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::{interval, material, ray, vec3};
+use crate::{aabb, interval, material, ray, vec3};
 
 pub struct HitRecord {
     p: vec3::Point3,
     normal: vec3::Vec3,
-    mat: Rc<dyn material::Material>,
+    mat: Arc<dyn material::Material>,
     t: f64,
     front_face: bool,
+    barycentric: (f64, f64), // Weights of the second and third triangle vertices
+    u: f64,                  // Surface coordinates of the hit point
+    v: f64,
 }
 
 impl HitRecord {
@@ -31,11 +34,16 @@ impl HitRecord {
         self.normal
     }
 
-    pub fn mat(&self) -> Rc<dyn material::Material> {
+    pub fn set_normal(&mut self, input: vec3::Vec3) {
+        // Replaces the normal as it is, already facing against the ray; front_face is kept.
+        self.normal = input;
+    }
+
+    pub fn mat(&self) -> Arc<dyn material::Material> {
         self.mat.clone()
     }
 
-    pub fn set_mat(&mut self, input: Rc<dyn material::Material>) {
+    pub fn set_mat(&mut self, input: Arc<dyn material::Material>) {
         self.mat = input
     }
 
@@ -43,6 +51,23 @@ impl HitRecord {
         self.front_face
     }
 
+    pub fn barycentric(&self) -> (f64, f64) {
+        self.barycentric
+    }
+
+    pub fn set_barycentric(&mut self, u: f64, v: f64) {
+        self.barycentric = (u, v);
+    }
+
+    pub fn uv(&self) -> (f64, f64) {
+        (self.u, self.v)
+    }
+
+    pub fn set_uv(&mut self, u: f64, v: f64) {
+        self.u = u;
+        self.v = v;
+    }
+
     pub fn set_face_normal(&mut self, r: ray::Ray, outward_normal: vec3::Vec3) {
         // Sets the hit record normal vector.
         // NOTE: the outward_normal is assumed to have unit length.
@@ -53,6 +78,16 @@ impl HitRecord {
             self.normal = -outward_normal;
         }
     }
+
+    pub fn set_shading_normal(&mut self, outward_normal: vec3::Vec3) {
+        // Replaces the normal with an interpolated one, keeping the side chosen by
+        // set_face_normal. NOTE: the outward_normal is assumed to have unit length.
+        self.normal = if self.front_face {
+            outward_normal
+        } else {
+            -outward_normal
+        };
+    }
 }
 
 impl Default for HitRecord {
@@ -60,13 +95,29 @@ impl Default for HitRecord {
         HitRecord {
             p: vec3::Vec3::default(),
             normal: vec3::Vec3::default(),
-            mat: Rc::new(material::Lambertian::default()),
+            mat: Arc::new(material::Lambertian::default()),
             t: 0.0,
             front_face: false,
+            barycentric: (0.0, 0.0),
+            u: 0.0,
+            v: 0.0,
         }
     }
 }
 
-pub trait Hittable {
+pub trait Hittable: Send + Sync {
     fn hit(&self, r: ray::Ray, ray_t: interval::Interval, rec: &mut HitRecord) -> bool;
+
+    fn bounding_box(&self) -> aabb::Aabb;
+
+    // Density, with respect to solid angle, of random() picking direction from origin; random()
+    // maps a point u of the unit square to a direction. Objects that can't be sampled as lights
+    // keep the defaults.
+    fn pdf_value(&self, _origin: vec3::Point3, _direction: vec3::Vec3) -> f64 {
+        0.0
+    }
+
+    fn random(&self, _origin: vec3::Point3, _u: (f64, f64)) -> vec3::Vec3 {
+        vec3::Vec3::new(1.0, 0.0, 0.0)
+    }
 }
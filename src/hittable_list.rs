@@ -1,22 +1,30 @@
-use crate::{hittable, interval};
+use std::sync::Arc;
 
+use crate::{aabb, hittable, interval, vec3};
+
+#[derive(Clone, Default)]
 pub struct HittableList {
-    objects: Vec<Box<dyn hittable::Hittable>>,
+    objects: Vec<Arc<dyn hittable::Hittable>>,
+    bbox: aabb::Aabb,
 }
 
 impl HittableList {
     pub fn new() -> Self {
-        Self {
-            objects: Vec::new(),
-        }
+        Self::default()
     }
 
-    pub fn add(&mut self, object: Box<dyn hittable::Hittable>) {
+    pub fn add(&mut self, object: Arc<dyn hittable::Hittable>) {
+        self.bbox = aabb::Aabb::surrounding(self.bbox, object.bounding_box());
         self.objects.push(object);
     }
 
     pub fn clear(&mut self) {
         self.objects = Vec::new();
+        self.bbox = aabb::Aabb::default();
+    }
+
+    pub fn objects(&self) -> &[Arc<dyn hittable::Hittable>] {
+        &self.objects
     }
 }
 
@@ -45,4 +53,31 @@ impl hittable::Hittable for HittableList {
 
         hit_anything
     }
+
+    fn bounding_box(&self) -> aabb::Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: vec3::Point3, direction: vec3::Vec3) -> f64 {
+        // random() picks each object with equal probability.
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let weight = 1.0 / self.objects.len() as f64;
+        self.objects
+            .iter()
+            .map(|object| weight * object.pdf_value(origin, direction))
+            .sum()
+    }
+
+    fn random(&self, origin: vec3::Point3, (u1, u2): (f64, f64)) -> vec3::Vec3 {
+        if self.objects.is_empty() {
+            return vec3::Vec3::new(1.0, 0.0, 0.0);
+        }
+        // The first coordinate picks an object and what is left of it is reused for the object.
+        let n = self.objects.len();
+        let scaled = u1 * n as f64;
+        let i = (scaled as usize).min(n - 1);
+        self.objects[i].random(origin, ((scaled - i as f64).min(1.0), u2))
+    }
 }
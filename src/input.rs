@@ -0,0 +1,307 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+use crate::{color, framebuffer};
+
+// Loads an image file into a framebuffer of linear RGB, undoing the sRGB encoding of 8-bit
+// formats; Radiance HDR and PFM images are linear already. The format is picked from the file
+// extension, as for output.
+pub fn read_image(path: &Path) -> io::Result<framebuffer::Framebuffer> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match ext.as_deref() {
+        Some("png") => read_png(path),
+        Some("jpg") | Some("jpeg") => read_jpeg(path),
+        Some("hdr") => read_hdr(path),
+        Some("pfm") => read_pfm(path),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unsupported image extension: {}", path.display()),
+        )),
+    }
+}
+
+fn read_png(path: &Path) -> io::Result<framebuffer::Framebuffer> {
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    // Expand palettes and low bit depths, and strip 16-bit channels down to 8 bits.
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(io::Error::other)?;
+    let size = reader
+        .output_buffer_size()
+        .ok_or_else(|| io::Error::other("PNG image is too large"))?;
+    let mut buf = vec![0; size];
+    let info = reader.next_frame(&mut buf).map_err(io::Error::other)?;
+
+    let channels = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        png::ColorType::Indexed => {
+            return Err(io::Error::other("PNG palette was not expanded"));
+        }
+    };
+    Ok(from_bytes(
+        info.width as usize,
+        info.height as usize,
+        channels,
+        &buf[..info.buffer_size()],
+    ))
+}
+
+fn read_jpeg(path: &Path) -> io::Result<framebuffer::Framebuffer> {
+    let mut decoder = jpeg_decoder::Decoder::new(BufReader::new(File::open(path)?));
+    let pixels = decoder.decode().map_err(io::Error::other)?;
+    let info = decoder
+        .info()
+        .ok_or_else(|| io::Error::other("JPEG image has no header"))?;
+    let channels = match info.pixel_format {
+        jpeg_decoder::PixelFormat::L8 => 1,
+        jpeg_decoder::PixelFormat::RGB24 => 3,
+        _ => {
+            return Err(io::Error::other(format!(
+                "unsupported JPEG pixel format {:?}",
+                info.pixel_format
+            )))
+        }
+    };
+    Ok(from_bytes(
+        info.width as usize,
+        info.height as usize,
+        channels,
+        &pixels,
+    ))
+}
+
+fn read_hdr(path: &Path) -> io::Result<framebuffer::Framebuffer> {
+    // Radiance RGBE: a text header ended by a blank line, a resolution line, then scanlines of
+    // shared-exponent pixels, each either flat or run-length encoded per channel.
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if !line.starts_with("#?") {
+        return Err(io::Error::other("not a Radiance HDR image"));
+    }
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::other("HDR header has no end"));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(io::Error::other(format!(
+                    "unsupported HDR format {}",
+                    format
+                )));
+            }
+        }
+    }
+
+    line.clear();
+    reader.read_line(&mut line)?;
+    let (width, height) = match line.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", height, "+X", width] => match (width.parse(), height.parse()) {
+            (Ok(width), Ok(height)) => (width, height),
+            _ => return Err(io::Error::other("invalid HDR image size")),
+        },
+        _ => {
+            return Err(io::Error::other(format!(
+                "unsupported HDR orientation {}",
+                line.trim_end()
+            )))
+        }
+    };
+
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    // Even fully run-length encoded, a scanline takes 2 bytes per 128 pixels of each channel,
+    // so a size the data can't possibly hold is rejected before allocating the image.
+    let pixels = checked_pixels(width, height)
+        .filter(|pixels| pixels / 16 <= data.len())
+        .ok_or_else(|| io::Error::other("invalid HDR image size"))?;
+    if pixels == 0 {
+        return Err(io::Error::other("HDR image is empty"));
+    }
+    let mut pos = 0;
+    let mut scanline = vec![[0u8; 4]; width];
+    let mut image = framebuffer::Framebuffer::new(width, height);
+    for y in 0..height {
+        read_hdr_scanline(&data, &mut pos, &mut scanline)?;
+        for (pixel, rgbe) in image.row_mut(y).iter_mut().zip(&scanline) {
+            *pixel = from_rgbe(*rgbe);
+        }
+    }
+    Ok(image)
+}
+
+fn read_hdr_scanline(data: &[u8], pos: &mut usize, scanline: &mut [[u8; 4]]) -> io::Result<()> {
+    let truncated = || io::Error::other("HDR image data is truncated");
+    let width = scanline.len();
+
+    // Run-length encoded scanlines start with 2, 2 and the width; anything else is flat.
+    let header = data.get(*pos..*pos + 4).ok_or_else(truncated)?;
+    let encoded = (8..0x8000).contains(&width)
+        && header[0] == 2
+        && header[1] == 2
+        && ((header[2] as usize) << 8 | header[3] as usize) == width;
+    if encoded {
+        *pos += 4;
+    }
+    let mut next = || -> io::Result<u8> {
+        let byte = *data.get(*pos).ok_or_else(truncated)?;
+        *pos += 1;
+        Ok(byte)
+    };
+
+    if !encoded {
+        for rgbe in scanline.iter_mut() {
+            for c in rgbe.iter_mut() {
+                *c = next()?;
+            }
+        }
+        return Ok(());
+    }
+
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = next()? as usize;
+            let (run, n) = if count > 128 {
+                (true, count - 128)
+            } else {
+                (false, count)
+            };
+            if n == 0 || x + n > width {
+                return Err(io::Error::other("invalid HDR run length"));
+            }
+            if run {
+                let value = next()?;
+                for rgbe in &mut scanline[x..x + n] {
+                    rgbe[channel] = value;
+                }
+            } else {
+                for rgbe in &mut scanline[x..x + n] {
+                    rgbe[channel] = next()?;
+                }
+            }
+            x += n;
+        }
+    }
+    Ok(())
+}
+
+fn from_rgbe([r, g, b, e]: [u8; 4]) -> color::Color {
+    // The three mantissas share the exponent e, biased by 128, with 8 bits of fraction.
+    if e == 0 {
+        return color::Color::default();
+    }
+    let scale = 2f64.powi(e as i32 - 136);
+    color::Color::new(r as f64 * scale, g as f64 * scale, b as f64 * scale)
+}
+
+fn read_pfm(path: &Path) -> io::Result<framebuffer::Framebuffer> {
+    // Portable float map as written by output::write_pfm: "PF" (RGB) or "Pf" (grey), the size,
+    // a scale whose sign gives the byte order, then f32 pixels bottom row first.
+    let data = fs::read(path)?;
+    let mut pos = 0;
+    let channels = match pfm_token(&data, &mut pos)?.as_str() {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return Err(io::Error::other("not a PFM image")),
+    };
+    let invalid = || io::Error::other("invalid PFM header");
+    let width: usize = pfm_token(&data, &mut pos)?.parse().map_err(|_| invalid())?;
+    let height: usize = pfm_token(&data, &mut pos)?.parse().map_err(|_| invalid())?;
+    let scale: f64 = pfm_token(&data, &mut pos)?.parse().map_err(|_| invalid())?;
+    // A single whitespace character separates the header from the pixels.
+    pos += 1;
+
+    let size = checked_pixels(width, height)
+        .and_then(|pixels| pixels.checked_mul(channels * 4))
+        .ok_or_else(|| io::Error::other("PFM image is too large"))?;
+    if size == 0 {
+        return Err(io::Error::other("PFM image is empty"));
+    }
+    let pixels = pos
+        .checked_add(size)
+        .and_then(|end| data.get(pos..end))
+        .ok_or_else(|| io::Error::other("PFM image data is truncated"))?;
+    let values: Vec<f64> = pixels
+        .chunks(4)
+        .map(|b| {
+            let bytes = [b[0], b[1], b[2], b[3]];
+            let value = if scale < 0.0 {
+                f32::from_le_bytes(bytes)
+            } else {
+                f32::from_be_bytes(bytes)
+            };
+            value as f64 * scale.abs()
+        })
+        .collect();
+
+    let mut image = framebuffer::Framebuffer::new(width, height);
+    for (y, row) in values.chunks(width * channels).rev().enumerate() {
+        for (pixel, px) in image.row_mut(y).iter_mut().zip(row.chunks(channels)) {
+            *pixel = if channels < 3 {
+                color::Color::new(px[0], px[0], px[0])
+            } else {
+                color::Color::new(px[0], px[1], px[2])
+            };
+        }
+    }
+    Ok(image)
+}
+
+fn checked_pixels(width: usize, height: usize) -> Option<usize> {
+    // Number of pixels, if a framebuffer of that many colors can be allocated at all.
+    let pixels = width.checked_mul(height)?;
+    pixels.checked_mul(std::mem::size_of::<color::Color>())?;
+    Some(pixels)
+}
+
+fn pfm_token(data: &[u8], pos: &mut usize) -> io::Result<String> {
+    while data.get(*pos).is_some_and(|b| b.is_ascii_whitespace()) {
+        *pos += 1;
+    }
+    let start = *pos;
+    while data.get(*pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+        *pos += 1;
+    }
+    if start == *pos {
+        return Err(io::Error::other("PFM header is truncated"));
+    }
+    Ok(String::from_utf8_lossy(&data[start..*pos]).into_owned())
+}
+
+fn from_bytes(
+    width: usize,
+    height: usize,
+    channels: usize,
+    data: &[u8],
+) -> framebuffer::Framebuffer {
+    let mut image = framebuffer::Framebuffer::new(width, height);
+    for y in 0..height {
+        let row = &data[y * width * channels..(y + 1) * width * channels];
+        for (pixel, px) in image.row_mut(y).iter_mut().zip(row.chunks(channels)) {
+            // Grey images repeat their single channel; any alpha channel is ignored.
+            let (r, g, b) = if channels < 3 {
+                (px[0], px[0], px[0])
+            } else {
+                (px[0], px[1], px[2])
+            };
+            *pixel = color::Color::new(
+                color::srgb_to_linear(r),
+                color::srgb_to_linear(g),
+                color::srgb_to_linear(b),
+            );
+        }
+    }
+    image
+}
@@ -0,0 +1,172 @@
+use std::sync::Arc;
+
+use crate::{aabb, hittable, interval, mat4, ray, vec3};
+
+// Places a shared object in the world with an affine transform, so one mesh or sphere can be
+// reused many times without copying it. Rays are carried into the object's space to be
+// intersected and the hit is carried back out.
+pub struct Instance {
+    object: Arc<dyn hittable::Hittable>,
+    to_world: mat4::Mat4,
+    to_object: mat4::Mat4,
+    normal_to_world: mat4::Mat4, // Inverse transpose; normals don't transform like directions
+    bbox: aabb::Aabb,
+}
+
+impl Instance {
+    // None if the transform is singular, e.g. a scale of zero along some axis.
+    pub fn new(object: Arc<dyn hittable::Hittable>, to_world: mat4::Mat4) -> Option<Self> {
+        let to_object = to_world.inverse()?;
+        let bbox = transform_box(object.bounding_box(), to_world);
+        Some(Self {
+            object,
+            to_world,
+            to_object,
+            normal_to_world: to_object.transpose(),
+            bbox,
+        })
+    }
+}
+
+impl hittable::Hittable for Instance {
+    fn hit(&self, r: ray::Ray, ray_t: interval::Interval, rec: &mut hittable::HitRecord) -> bool {
+        // The direction isn't renormalised, so t means the same distance along both rays.
+        let object_ray = r.with_line(
+            self.to_object.transform_point(r.origin()),
+            self.to_object.transform_vector(r.direction()),
+        );
+        if !self.object.hit(object_ray, ray_t, rec) {
+            return false;
+        }
+
+        rec.set_p(self.to_world.transform_point(rec.p()));
+        let normal = self.normal_to_world.transform_vector(rec.normal());
+        rec.set_normal(vec3::unit_vector(normal));
+
+        true
+    }
+
+    fn bounding_box(&self) -> aabb::Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: vec3::Point3, direction: vec3::Vec3) -> f64 {
+        let object_pdf = self.object.pdf_value(
+            self.to_object.transform_point(origin),
+            self.to_object.transform_vector(direction),
+        );
+        if object_pdf == 0.0 {
+            return 0.0;
+        }
+        // The transform stretches directions as well as positions. The density of directions
+        // changes by |det M^-1| / |M^-1 w|^3 for a unit world direction w, which is 1 for a
+        // rotation and a uniform scale.
+        let w = vec3::unit_vector(direction);
+        let stretch = self.to_object.transform_vector(w).length();
+        object_pdf * self.to_object.determinant().abs() / (stretch * stretch * stretch)
+    }
+
+    fn random(&self, origin: vec3::Point3, u: (f64, f64)) -> vec3::Vec3 {
+        let direction = self
+            .object
+            .random(self.to_object.transform_point(origin), u);
+        self.to_world.transform_vector(direction)
+    }
+}
+
+fn transform_box(bbox: aabb::Aabb, m: mat4::Mat4) -> aabb::Aabb {
+    // Arvo's method: each output axis is the translation plus, for every input axis, whichever
+    // end of its interval the matrix entry makes smaller or larger. Zero entries are skipped so
+    // an unbounded input axis that doesn't contribute can't turn into NaN.
+    let axis = |i: usize| {
+        let (mut min, mut max) = (m.get(i, 3), m.get(i, 3));
+        for j in 0..3 {
+            let e = m.get(i, j);
+            if e == 0.0 {
+                continue;
+            }
+            let input = bbox.axis_interval(j);
+            let (a, b) = (e * input.min(), e * input.max());
+            min += a.min(b);
+            max += a.max(b);
+        }
+        interval::Interval::new(min, max)
+    };
+    aabb::Aabb::new(axis(0), axis(1), axis(2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material, sphere, triangle};
+
+    // The hit t and normal of a ray against an object, if it hits.
+    fn trace(object: &dyn hittable::Hittable, r: ray::Ray) -> Option<(f64, vec3::Vec3)> {
+        let mut rec = hittable::HitRecord::default();
+        object
+            .hit(r, interval::Interval::new(0.001, f64::INFINITY), &mut rec)
+            .then(|| (rec.t(), rec.normal()))
+    }
+
+    fn assert_same_hits(instanced: &dyn hittable::Hittable, direct: &dyn hittable::Hittable) {
+        let mut hits = 0;
+        for i in 0..20 {
+            for j in 0..20 {
+                let target = vec3::Point3::new(i as f64 * 0.3 - 3.0, j as f64 * 0.3 - 3.0, 0.0);
+                let origin = vec3::Point3::new(0.5, 1.0, 10.0);
+                let r = ray::Ray::new(origin, target - origin);
+                match (trace(instanced, r), trace(direct, r)) {
+                    (Some((t1, n1)), Some((t2, n2))) => {
+                        assert!((t1 - t2).abs() < 1e-9, "t {} != {}", t1, t2);
+                        assert!((n1 - n2).length() < 1e-9, "normal {:?} != {:?}", n1, n2);
+                        hits += 1;
+                    }
+                    (None, None) => {}
+                    (a, b) => panic!("instance hit {:?} but direct hit {:?}", a, b),
+                }
+            }
+        }
+        assert!(hits > 10, "only {} rays hit", hits);
+    }
+
+    #[test]
+    fn transformed_triangle_matches_one_built_in_world_space() {
+        let mat: Arc<dyn material::Material> = Arc::new(material::Lambertian::default());
+        let to_world = mat4::Mat4::translation(vec3::Vec3::new(0.5, -0.5, 1.0))
+            * mat4::Mat4::rotation(vec3::Vec3::new(0.0, 1.0, 0.2), 30.0)
+            * mat4::Mat4::scaling(vec3::Vec3::new(3.0, 2.0, 0.5));
+        let corners = [
+            vec3::Point3::new(-1.0, -1.0, 0.0),
+            vec3::Point3::new(1.0, -1.0, 0.0),
+            vec3::Point3::new(0.0, 1.0, 0.5),
+        ];
+        let local = Arc::new(triangle::Triangle::new(
+            corners[0],
+            corners[1],
+            corners[2],
+            mat.clone(),
+        ));
+        let [a, b, c] = corners.map(|p| to_world.transform_point(p));
+        let direct = triangle::Triangle::new(a, b, c, mat);
+
+        let instanced = Instance::new(local, to_world).unwrap();
+        assert_same_hits(&instanced, &direct);
+    }
+
+    #[test]
+    fn transformed_sphere_matches_one_built_in_world_space() {
+        let mat: Arc<dyn material::Material> = Arc::new(material::Lambertian::default());
+        let to_world = mat4::Mat4::translation(vec3::Vec3::new(-1.0, 0.5, 0.0))
+            * mat4::Mat4::rotation(vec3::Vec3::new(1.0, 0.0, 0.0), 45.0)
+            * mat4::Mat4::scaling(vec3::Vec3::new(2.0, 2.0, 2.0));
+        let local = Arc::new(sphere::Sphere::new(
+            vec3::Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            mat.clone(),
+        ));
+        let direct = sphere::Sphere::new(vec3::Point3::new(-1.0, 0.5, 0.0), 2.0, mat);
+
+        let instanced = Instance::new(local, to_world).unwrap();
+        assert_same_hits(&instanced, &direct);
+    }
+}
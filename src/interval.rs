@@ -7,10 +7,18 @@ pub struct Interval {
 }
 
 impl Interval {
-    pub fn new(min: f64, max: f64) -> Self {
+    pub const fn new(min: f64, max: f64) -> Self {
         Self { min, max }
     }
 
+    pub fn enclosing(a: Interval, b: Interval) -> Self {
+        // Create the interval tightly enclosing the two input intervals.
+        Self {
+            min: a.min.min(b.min),
+            max: a.max.max(b.max),
+        }
+    }
+
     pub fn size(&self) -> f64 {
         self.max - self.min
     }
@@ -19,6 +27,11 @@ impl Interval {
         self.min <= x && x <= self.max
     }
 
+    pub fn expand(&self, delta: f64) -> Self {
+        let padding = delta / 2.0;
+        Self::new(self.min - padding, self.max + padding)
+    }
+
     pub fn surrounds(&self, x: f64) -> bool {
         self.min < x && x < self.max
     }
@@ -43,10 +56,10 @@ impl Interval {
 
 impl Default for Interval {
     fn default() -> Self {
-        Interval::new(f64::INFINITY, f64::NEG_INFINITY)
+        EMPTY
     }
 }
 
-// pub const EMPTY: Interval = Interval::default();
+pub const EMPTY: Interval = Interval::new(f64::INFINITY, f64::NEG_INFINITY);
 
-// pub const UNIVERSE: Interval = Interval::new(f64::NEG_INFINITY, f64::INFINITY);
+pub const UNIVERSE: Interval = Interval::new(f64::NEG_INFINITY, f64::INFINITY);
@@ -1,101 +1,181 @@
-use std::rc::Rc;
+use std::error;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+use std::time::{Duration, Instant};
 
+use clap::Parser;
+
+mod aabb;
+mod bvh;
 mod camera;
+mod cli;
 mod color;
+mod constant_medium;
+mod disk;
+mod environment;
+mod film;
+mod framebuffer;
 mod hittable;
 mod hittable_list;
+mod input;
+mod instance;
 mod interval;
+mod mat4;
 mod material;
+mod mesh;
+mod microfacet;
+mod obj;
+mod onb;
+mod output;
+mod pdf;
+mod perlin;
+mod plane;
+mod preview;
+mod quad;
 mod ray;
+mod sampler;
+mod scene;
+mod spectrum;
 mod sphere;
+mod texture;
+mod triangle;
 mod utils;
 mod vec3;
 
 fn main() {
-    // Image
-    let aspect_ratio: f64 = 16.0 / 9.0;
-    const IMAGE_WIDTH: i64 = 1200;
-    const SAMPLES_PER_PIXEL: i64 = 500;
-    const MAX_DEPTH: i64 = 50;
-    const VFOV: f64 = 20.0;
-    const DEFOCUS_ANGLE: f64 = 0.6; // Variantion angle of rays through each pixel
-    const FOCUS_DIST: f64 = 10.0; // Distance from camera lookfrom point to plane of perfect focus
-    let lookfrom = vec3::Point3::new(13.0, 2.0, 3.0); // Point camera is looking from
-    let lookat = vec3::Point3::new(0.0, 0.0, 0.0); // Point camear is looking at
-    let vup = vec3::Vec3::new(0.0, 1.0, 0.0); // Camera-relative up direction
+    if let Err(err) = run() {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn error::Error>> {
+    let cli = cli::Cli::parse();
+
+    // An explicit --format wins; otherwise it follows the output extension, defaulting to P3
+    // on stdout.
+    let output_format = match (cli.format, &cli.output) {
+        (Some(format), _) => format,
+        (None, Some(path)) => output::Format::from_path(path)?,
+        (None, None) => output::Format::PpmAscii,
+    };
+    let heatmap = match &cli.heatmap {
+        Some(path) => Some((path, output::Format::from_path(path)?)),
+        None => None,
+    };
+    let mut preview_file = match &cli.preview {
+        Some(path) => Some(preview::PreviewFile::new(
+            path.clone(),
+            Duration::from_secs(cli.preview_interval),
+            cli.preview_passes,
+        )?),
+        None => None,
+    };
+
+    // A resumed render keeps the seed it was started with, so it rebuilds the same scene and
+    // carries on the same sample streams.
+    let resumed = match (&cli.checkpoint, cli.resume) {
+        (Some(path), true) => Some(film::Film::load(path)?),
+        _ => None,
+    };
+    let seed = match (&resumed, cli.seed) {
+        (Some(film), Some(seed)) if seed != film.seed() => {
+            return Err(format!(
+                "the checkpoint was rendered with seed {}, not {}",
+                film.seed(),
+                seed
+            )
+            .into());
+        }
+        (Some(film), _) => film.seed(),
+        (None, seed) => seed.unwrap_or_else(utils::random_seed),
+    };
+    eprintln!("Seed: {}", seed);
+
+    let mut rng = utils::Rng::new(seed);
+    let mut scene = match scene::builtin(&cli.scene, &mut rng) {
+        Some(scene) => scene,
+        None => scene::load(Path::new(&cli.scene), &mut rng)?,
+    };
+    cli.apply(&mut scene.camera)?;
+
+    let mut cam = scene.camera.build();
+    cam.set_seed(seed);
+    if let Some(threads) = cli.threads {
+        cam.set_threads(threads);
+    }
+    let world = bvh::BvhNode::new(&scene.world);
+    if cli.brute_force {
+        scene.lights.clear();
+    }
 
-    // World
-    let mut world = hittable_list::HittableList::new();
+    // Samples can only be added to a checkpoint taken of the same scene with the same settings.
+    let settings = utils::hash(&[
+        scene.source,
+        scene.camera.fingerprint(),
+        cli.brute_force as u64,
+    ]);
 
-    let ground_material = Rc::new(material::Lambertian::new(color::Color::new(0.5, 0.5, 0.5)));
-    world.add(Box::new(sphere::Sphere::new(
-        vec3::Point3::new(0.0, -1000.0, 0.0),
-        1000.0,
-        ground_material,
-    )));
+    let (width, height) = cam.image_size();
+    let mut film = match resumed {
+        Some(film) => {
+            film.check_resumable(width, height, settings)?;
+            film
+        }
+        None => film::Film::new(width, height, seed, settings),
+    };
 
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat = utils::random_double();
-            let center = vec3::Point3::new(
-                (a as f64) + 0.9 * utils::random_double(),
-                0.2,
-                (b as f64) + 0.9 * utils::random_double(),
-            );
+    #[cfg(feature = "window")]
+    let mut preview_window = if cli.window {
+        Some(preview::PreviewWindow::new(width, height).map_err(|err| err.to_string())?)
+    } else {
+        None
+    };
 
-            if (center - vec3::Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                if choose_mat < 0.8 {
-                    // diffuse
-                    let albedo = color::Color::new_random() * color::Color::new_random();
-                    let sphere_material = Rc::new(material::Lambertian::new(albedo));
-                    world.add(Box::new(sphere::Sphere::new(center, 0.2, sphere_material)));
-                } else if choose_mat < 0.95 {
-                    // metal
-                    let albedo = color::Color::new_random_bounded(0.5, 1.0);
-                    let fuzz = utils::random_double_bounded(0.0, 0.5);
-                    let sphere_material = Rc::new(material::Metal::new(albedo, fuzz));
-                    world.add(Box::new(sphere::Sphere::new(center, 0.2, sphere_material)));
-                } else {
-                    // glass
-                    let sphere_material = Rc::new(material::Dielectric::new(1.5));
-                    world.add(Box::new(sphere::Sphere::new(center, 0.2, sphere_material)));
-                }
+    let checkpoint_interval = Duration::from_secs(cli.checkpoint_interval);
+    let mut last_checkpoint = Instant::now();
+    let mut after_pass = |film: &film::Film| -> io::Result<()> {
+        if let Some(preview) = preview_file.as_mut() {
+            preview.after_pass(film)?;
+        }
+        #[cfg(feature = "window")]
+        if let Some(window) = preview_window.as_mut() {
+            window.after_pass(film)?;
+        }
+        if let Some(path) = &cli.checkpoint {
+            if last_checkpoint.elapsed() >= checkpoint_interval {
+                film.save(path)?;
+                last_checkpoint = Instant::now();
             }
         }
+        Ok(())
+    };
+    cam.render(&world, &scene.lights, &mut film, &mut after_pass)?;
+    if let Some(path) = &cli.checkpoint {
+        film.save(path)?;
+    }
+    if let Some(preview) = preview_file.as_mut() {
+        preview.write(&film)?;
     }
 
-    let material_1 = Rc::new(material::Dielectric::new(1.5));
-    world.add(Box::new(sphere::Sphere::new(
-        vec3::Point3::new(0.0, 1.0, 0.0),
-        1.0,
-        material_1,
-    )));
-
-    let material_2 = Rc::new(material::Lambertian::new(color::Color::new(0.4, 0.2, 0.1)));
-    world.add(Box::new(sphere::Sphere::new(
-        vec3::Point3::new(-4.0, 1.0, 0.0),
-        1.0,
-        material_2,
-    )));
+    if let Some((path, format)) = heatmap {
+        output::write_image(&film.samples_heatmap(), format, path)?;
+    }
+    let image = film.image();
 
-    let material_3 = Rc::new(material::Metal::new(color::Color::new(0.7, 0.6, 0.5), 0.0));
-    world.add(Box::new(sphere::Sphere::new(
-        vec3::Point3::new(4.0, 1.0, 0.0),
-        1.0,
-        material_3,
-    )));
+    match &cli.output {
+        Some(path) => output::write_image(&image, output_format, path)?,
+        None => {
+            let mut out = io::BufWriter::new(io::stdout().lock());
+            output::write_format(&image, output_format, &mut out)?;
+            out.flush()?;
+        }
+    }
 
-    let cam = camera::Camera::new(
-        aspect_ratio,
-        IMAGE_WIDTH,
-        SAMPLES_PER_PIXEL,
-        MAX_DEPTH,
-        VFOV,
-        lookfrom,
-        lookat,
-        vup,
-        DEFOCUS_ANGLE,
-        FOCUS_DIST,
-    );
-    cam.render(&world);
+    #[cfg(feature = "window")]
+    if let Some(window) = preview_window.as_mut() {
+        window.wait_until_closed();
+    }
+    Ok(())
 }
@@ -0,0 +1,181 @@
+use std::ops::Mul;
+
+use crate::{utils, vec3};
+
+// A 4x4 matrix acting on column vectors, stored by rows. Only affine transforms are built, so
+// the bottom row stays (0, 0, 0, 1).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Mat4([[f64; 4]; 4]);
+
+impl Mat4 {
+    pub fn new(rows: [[f64; 4]; 4]) -> Self {
+        Mat4(rows)
+    }
+
+    pub fn identity() -> Self {
+        Mat4::scaling(vec3::Vec3::new(1.0, 1.0, 1.0))
+    }
+
+    pub fn translation(offset: vec3::Vec3) -> Self {
+        Mat4([
+            [1.0, 0.0, 0.0, offset.x()],
+            [0.0, 1.0, 0.0, offset.y()],
+            [0.0, 0.0, 1.0, offset.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scaling(factors: vec3::Vec3) -> Self {
+        Mat4([
+            [factors.x(), 0.0, 0.0, 0.0],
+            [0.0, factors.y(), 0.0, 0.0],
+            [0.0, 0.0, factors.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn rotation(axis: vec3::Vec3, degrees: f64) -> Self {
+        // Rodrigues' rotation, counterclockwise about axis when looking down it towards the
+        // origin.
+        let a = vec3::unit_vector(axis);
+        let (sin, cos) = utils::degrees_to_radians(degrees).sin_cos();
+        let t = 1.0 - cos;
+        let (x, y, z) = (a.x(), a.y(), a.z());
+        Mat4([
+            [
+                t * x * x + cos,
+                t * x * y - sin * z,
+                t * x * z + sin * y,
+                0.0,
+            ],
+            [
+                t * x * y + sin * z,
+                t * y * y + cos,
+                t * y * z - sin * x,
+                0.0,
+            ],
+            [
+                t * x * z - sin * y,
+                t * y * z + sin * x,
+                t * z * z + cos,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f64 {
+        self.0[row][col]
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut out = [[0.0; 4]; 4];
+        for (i, row) in out.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = self.0[j][i];
+            }
+        }
+        Mat4(out)
+    }
+
+    pub fn determinant(&self) -> f64 {
+        // Of the upper 3x3, which for an affine transform is the determinant of the whole.
+        let m = &self.0;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    pub fn inverse(&self) -> Option<Self> {
+        // Inverts an affine transform: the inverse of the 3x3 part by its adjugate, and the
+        // translation carried back through it. None for a singular matrix.
+        let det = self.determinant();
+        if det.abs() < 1e-12 || !det.is_finite() {
+            return None;
+        }
+        let m = &self.0;
+        let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| {
+            m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+        };
+        let inv_det = 1.0 / det;
+        let mut out = [[0.0; 4]; 4];
+        out[0][0] = cofactor(1, 2, 1, 2) * inv_det;
+        out[0][1] = -cofactor(0, 2, 1, 2) * inv_det;
+        out[0][2] = cofactor(0, 1, 1, 2) * inv_det;
+        out[1][0] = -cofactor(1, 2, 0, 2) * inv_det;
+        out[1][1] = cofactor(0, 2, 0, 2) * inv_det;
+        out[1][2] = -cofactor(0, 1, 0, 2) * inv_det;
+        out[2][0] = cofactor(1, 2, 0, 1) * inv_det;
+        out[2][1] = -cofactor(0, 2, 0, 1) * inv_det;
+        out[2][2] = cofactor(0, 1, 0, 1) * inv_det;
+        let t = vec3::Vec3::new(m[0][3], m[1][3], m[2][3]);
+        let linear = Mat4(out);
+        let back = linear.transform_vector(t);
+        out[0][3] = -back.x();
+        out[1][3] = -back.y();
+        out[2][3] = -back.z();
+        out[3][3] = 1.0;
+        Some(Mat4(out))
+    }
+
+    pub fn transform_point(&self, p: vec3::Point3) -> vec3::Point3 {
+        self.transform_vector(p) + vec3::Vec3::new(self.0[0][3], self.0[1][3], self.0[2][3])
+    }
+
+    pub fn transform_vector(&self, v: vec3::Vec3) -> vec3::Vec3 {
+        let m = &self.0;
+        let row = |r: [f64; 4]| r[0] * v.x() + r[1] * v.y() + r[2] * v.z();
+        vec3::Vec3::new(row(m[0]), row(m[1]), row(m[2]))
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut out = [[0.0; 4]; 4];
+        for (i, row) in out.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = (0..4).map(|k| self.0[i][k] * rhs.0[k][j]).sum();
+            }
+        }
+        Mat4(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_identity(m: Mat4) {
+        for i in 0..4 {
+            for j in 0..4 {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((m.get(i, j) - expected).abs() < 1e-12, "{:?}", m);
+            }
+        }
+    }
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        let m = Mat4::translation(vec3::Vec3::new(1.0, -2.0, 3.5))
+            * Mat4::rotation(vec3::Vec3::new(1.0, 2.0, -0.5), 37.0)
+            * Mat4::scaling(vec3::Vec3::new(2.0, 0.5, -3.0));
+        let inverse = m.inverse().unwrap();
+        assert_identity(inverse * m);
+        assert_identity(m * inverse);
+
+        let shear = Mat4::new([
+            [1.0, 0.7, 0.0, 4.0],
+            [0.0, 1.0, -0.3, 0.0],
+            [0.2, 0.0, 1.0, -1.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        assert_identity(shear.inverse().unwrap() * shear);
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        let flat = Mat4::scaling(vec3::Vec3::new(1.0, 0.0, 1.0));
+        assert_eq!(flat.inverse(), None);
+    }
+}
@@ -1,23 +1,110 @@
-use crate::{color, hittable, ray, utils, vec3};
+use std::sync::Arc;
 
-pub trait Material {
+use serde::Deserialize;
+
+use crate::{color, hittable, microfacet, pdf, ray, spectrum, texture, utils, vec3};
+
+// How a material scatters an incoming ray. Diffuse materials describe the outgoing direction
+// with a pdf so the renderer can mix it with light sampling; specular ones have a single
+// direction and skip the pdf entirely.
+#[derive(Default)]
+pub struct ScatterRecord {
+    attenuation: color::Color,
+    pdf: Option<Box<dyn pdf::Pdf>>,
+    skip_pdf_ray: ray::Ray,
+    dispersive: bool, // The scattered direction depends on the ray's wavelength
+}
+
+impl ScatterRecord {
+    pub fn attenuation(&self) -> color::Color {
+        self.attenuation
+    }
+
+    pub fn set_attenuation(&mut self, input: color::Color) {
+        self.attenuation = input;
+    }
+
+    pub fn pdf(&self) -> Option<&dyn pdf::Pdf> {
+        self.pdf.as_deref()
+    }
+
+    pub fn set_pdf(&mut self, input: Box<dyn pdf::Pdf>) {
+        self.pdf = Some(input);
+    }
+
+    pub fn skip_pdf_ray(&self) -> ray::Ray {
+        self.skip_pdf_ray
+    }
+
+    pub fn set_skip_pdf_ray(&mut self, input: ray::Ray) {
+        self.pdf = None;
+        self.skip_pdf_ray = input;
+    }
+
+    pub fn is_dispersive(&self) -> bool {
+        self.dispersive
+    }
+
+    pub fn set_dispersive(&mut self) {
+        self.dispersive = true;
+    }
+}
+
+pub trait Material: Send + Sync {
     fn scatter(
         &self,
         r_in: ray::Ray,
         rec: &mut hittable::HitRecord,
-        attenuation: &mut color::Color,
-        scattered: &mut ray::Ray,
+        srec: &mut ScatterRecord,
+        rng: &mut utils::Rng,
     ) -> bool;
+
+    // Density of the material's own scattering towards scattered, so that attenuation times
+    // this value is the BRDF times the cosine term. Only materials that set a pdf need it.
+    fn scattering_pdf(
+        &self,
+        _r_in: ray::Ray,
+        _rec: &hittable::HitRecord,
+        _scattered: ray::Ray,
+    ) -> f64 {
+        0.0
+    }
+
+    // The BSDF times the cosine term towards scattered. Materials whose BSDF is their
+    // attenuation times their scattering_pdf keep this default; others, whose color changes
+    // with the direction, compute it here instead.
+    fn scattering(
+        &self,
+        r_in: ray::Ray,
+        rec: &hittable::HitRecord,
+        srec: &ScatterRecord,
+        scattered: ray::Ray,
+    ) -> color::Color {
+        srec.attenuation() * self.scattering_pdf(r_in, rec, scattered)
+    }
+
+    fn emitted(&self, _u: f64, _v: f64, _p: vec3::Point3) -> color::Color {
+        color::Color::default()
+    }
 }
 
-#[derive(Default)]
 pub struct Lambertian {
-    albedo: color::Color,
+    tex: Arc<dyn texture::Texture>,
 }
 
 impl Lambertian {
     pub fn new(albedo: color::Color) -> Self {
-        Self { albedo }
+        Self::from_texture(Arc::new(texture::SolidColor::new(albedo)))
+    }
+
+    pub fn from_texture(tex: Arc<dyn texture::Texture>) -> Self {
+        Self { tex }
+    }
+}
+
+impl Default for Lambertian {
+    fn default() -> Self {
+        Self::new(color::Color::default())
     }
 }
 
@@ -26,29 +113,39 @@ impl Material for Lambertian {
         &self,
         _r_in: ray::Ray,
         rec: &mut hittable::HitRecord,
-        attenuation: &mut color::Color,
-        scattered: &mut ray::Ray,
+        srec: &mut ScatterRecord,
+        _rng: &mut utils::Rng,
     ) -> bool {
-        let mut scatter_direction = rec.normal() + vec3::random_unit_vector();
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal();
-        }
-        *scattered = ray::Ray::new(rec.p(), scatter_direction);
-        *attenuation = self.albedo;
+        let (u, v) = rec.uv();
+        srec.set_attenuation(self.tex.value(u, v, rec.p()));
+        srec.set_pdf(Box::new(pdf::CosinePdf::new(rec.normal())));
         true
     }
+
+    fn scattering_pdf(
+        &self,
+        _r_in: ray::Ray,
+        rec: &hittable::HitRecord,
+        scattered: ray::Ray,
+    ) -> f64 {
+        let cos_theta = vec3::dot(rec.normal(), vec3::unit_vector(scattered.direction()));
+        (cos_theta / utils::PI).max(0.0)
+    }
 }
 
-#[derive(Default)]
 pub struct Metal {
-    albedo: color::Color,
+    tex: Arc<dyn texture::Texture>,
     fuzz: f64,
 }
 
 impl Metal {
     pub fn new(albedo: color::Color, fuzz: f64) -> Self {
+        Self::from_texture(Arc::new(texture::SolidColor::new(albedo)), fuzz)
+    }
+
+    pub fn from_texture(tex: Arc<dyn texture::Texture>, fuzz: f64) -> Self {
         Self {
-            albedo,
+            tex,
             fuzz: if fuzz < 1.0 { fuzz } else { 1.0 },
         }
     }
@@ -59,14 +156,36 @@ impl Material for Metal {
         &self,
         r_in: ray::Ray,
         rec: &mut hittable::HitRecord,
-        attenuation: &mut color::Color,
-        scattered: &mut ray::Ray,
+        srec: &mut ScatterRecord,
+        rng: &mut utils::Rng,
     ) -> bool {
         let mut reflected = vec3::reflect(r_in.direction(), rec.normal());
-        reflected = vec3::unit_vector(reflected) + (self.fuzz * vec3::random_unit_vector());
-        *scattered = ray::Ray::new(rec.p(), reflected);
-        *attenuation = self.albedo;
-        vec3::dot(scattered.direction(), rec.normal()) > 0.0
+        reflected = vec3::unit_vector(reflected) + (self.fuzz * vec3::random_unit_vector(rng));
+        srec.set_skip_pdf_ray(ray::Ray::new_with_time(rec.p(), reflected, r_in.time()));
+        let (u, v) = rec.uv();
+        srec.set_attenuation(self.tex.value(u, v, rec.p()));
+        vec3::dot(reflected, rec.normal()) > 0.0
+    }
+}
+
+// How a dielectric's refractive index varies with wavelength, given in micrometers as glass
+// catalogs do.
+#[derive(Debug, Copy, Clone)]
+pub enum Dispersion {
+    Cauchy { a: f64, b: f64 },              // n = a + b / lambda^2
+    Sellmeier { b: [f64; 3], c: [f64; 3] }, // n^2 = 1 + sum of b lambda^2 / (lambda^2 - c)
+}
+
+impl Dispersion {
+    pub fn index(&self, wavelength: f64) -> f64 {
+        let l2 = (wavelength / 1000.0).powi(2);
+        match *self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let n2 = 1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f64>();
+                n2.max(1.0).sqrt()
+            }
+        }
     }
 }
 
@@ -75,11 +194,23 @@ pub struct Dielectric {
     // Refractive index in vacuum or air, or the ratio of the material's refractive index over
     // the refractive index of the enclosing media
     refraction_index: f64,
+    dispersion: Option<Dispersion>, // Per-wavelength index used when rendering spectrally
 }
 
 impl Dielectric {
     pub fn new(refraction_index: f64) -> Self {
-        Self { refraction_index }
+        Self {
+            refraction_index,
+            dispersion: None,
+        }
+    }
+
+    pub fn dispersive(dispersion: Dispersion) -> Self {
+        // Renders in RGB use the index at the reference wavelength.
+        Self {
+            refraction_index: dispersion.index(spectrum::REFERENCE_WAVELENGTH),
+            dispersion: Some(dispersion),
+        }
     }
 
     fn reflectance(&self, cosine: f64, refraction_index: f64) -> f64 {
@@ -94,14 +225,21 @@ impl Material for Dielectric {
         &self,
         r_in: ray::Ray,
         rec: &mut hittable::HitRecord,
-        attenuation: &mut color::Color,
-        scattered: &mut ray::Ray,
+        srec: &mut ScatterRecord,
+        rng: &mut utils::Rng,
     ) -> bool {
-        *attenuation = color::Color::new(1.0, 1.0, 1.0);
+        srec.set_attenuation(color::Color::new(1.0, 1.0, 1.0));
+        let refraction_index = match (self.dispersion, r_in.wavelength()) {
+            (Some(dispersion), Some(wavelength)) => {
+                srec.set_dispersive();
+                dispersion.index(wavelength)
+            }
+            _ => self.refraction_index,
+        };
         let ri = if rec.front_face() {
-            1.0 / self.refraction_index
+            1.0 / refraction_index
         } else {
-            self.refraction_index
+            refraction_index
         };
 
         let unit_direction = vec3::unit_vector(r_in.direction());
@@ -112,12 +250,678 @@ impl Material for Dielectric {
         let cannot_refract = ri * sin_theta > 1.0;
 
         let direction =
-            if cannot_refract || self.reflectance(cos_theta, ri) > utils::random_double() {
+            if cannot_refract || self.reflectance(cos_theta, ri) > utils::random_double(rng) {
                 vec3::reflect(unit_direction, rec.normal())
             } else {
                 vec3::refract(unit_direction, rec.normal(), ri)
             };
-        *scattered = ray::Ray::new(rec.p(), direction);
+        srec.set_skip_pdf_ray(ray::Ray::new_with_time(rec.p(), direction, r_in.time()));
+        true
+    }
+}
+
+// Metals with a measured complex index of refraction, as RGB averages of the spectral data.
+#[derive(Debug, Copy, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConductorPreset {
+    Gold,
+    Copper,
+    Aluminium,
+}
+
+impl ConductorPreset {
+    // The real and imaginary parts of the index, eta and k.
+    pub fn ior(&self) -> (color::Color, color::Color) {
+        match self {
+            ConductorPreset::Gold => (
+                color::Color::new(0.143119, 0.374957, 1.44248),
+                color::Color::new(3.98316, 2.38572, 1.60322),
+            ),
+            ConductorPreset::Copper => (
+                color::Color::new(0.200438, 0.924033, 1.10221),
+                color::Color::new(3.91295, 2.45285, 2.14219),
+            ),
+            ConductorPreset::Aluminium => (
+                color::Color::new(1.65746, 0.880369, 0.521229),
+                color::Color::new(9.22387, 6.26952, 4.837),
+            ),
+        }
+    }
+}
+
+// A metal described by its complex index of refraction, with GGX microfacet roughness. Unlike
+// Metal's fuzz, the color comes from the Fresnel equations, so it shifts towards white at
+// grazing angles.
+pub struct Conductor {
+    eta: color::Color,
+    k: color::Color,
+    ggx: microfacet::Ggx,
+}
+
+impl Conductor {
+    pub fn new(eta: color::Color, k: color::Color, roughness: f64, anisotropy: f64) -> Self {
+        Self {
+            eta,
+            k,
+            ggx: microfacet::Ggx::new(roughness, anisotropy),
+        }
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(
+        &self,
+        r_in: ray::Ray,
+        rec: &mut hittable::HitRecord,
+        srec: &mut ScatterRecord,
+        _rng: &mut utils::Rng,
+    ) -> bool {
+        let unit_direction = vec3::unit_vector(r_in.direction());
+        if self.ggx.is_smooth() {
+            let cos_theta = vec3::dot(-unit_direction, rec.normal());
+            let reflected = vec3::reflect(unit_direction, rec.normal());
+            srec.set_attenuation(microfacet::fresnel_conductor(cos_theta, self.eta, self.k));
+            srec.set_skip_pdf_ray(ray::Ray::new_with_time(rec.p(), reflected, r_in.time()));
+            return true;
+        }
+        let frame = microfacet::shading_frame(rec.normal());
+        let wo = frame.inverse_transform(-unit_direction);
+        srec.set_pdf(Box::new(microfacet::ReflectionPdf::new(
+            frame, wo, self.ggx,
+        )));
+        true
+    }
+
+    fn scattering(
+        &self,
+        r_in: ray::Ray,
+        rec: &hittable::HitRecord,
+        _srec: &ScatterRecord,
+        scattered: ray::Ray,
+    ) -> color::Color {
+        let frame = microfacet::shading_frame(rec.normal());
+        let wo = frame.inverse_transform(-vec3::unit_vector(r_in.direction()));
+        let wi = frame.inverse_transform(vec3::unit_vector(scattered.direction()));
+        microfacet::conductor_bsdf(&self.ggx, wo, wi, self.eta, self.k)
+    }
+}
+
+// Glass with a GGX microfacet surface, such as frosted or etched glass. Light is reflected or
+// transmitted in proportion to the exact Fresnel reflectance of each facet.
+pub struct RoughDielectric {
+    refraction_index: f64,
+    ggx: microfacet::Ggx,
+}
+
+impl RoughDielectric {
+    pub fn new(refraction_index: f64, roughness: f64, anisotropy: f64) -> Self {
+        Self {
+            refraction_index,
+            ggx: microfacet::Ggx::new(roughness, anisotropy),
+        }
+    }
+
+    fn relative_index(&self, rec: &hittable::HitRecord) -> f64 {
+        // Index of the far side of the surface relative to the side the ray comes from.
+        if rec.front_face() {
+            self.refraction_index
+        } else {
+            1.0 / self.refraction_index
+        }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(
+        &self,
+        r_in: ray::Ray,
+        rec: &mut hittable::HitRecord,
+        srec: &mut ScatterRecord,
+        rng: &mut utils::Rng,
+    ) -> bool {
+        let eta = self.relative_index(rec);
+        let unit_direction = vec3::unit_vector(r_in.direction());
+        if self.ggx.is_smooth() {
+            // Total internal reflection has a reflectance of one, so it always reflects.
+            let cos_theta = vec3::dot(-unit_direction, rec.normal()).min(1.0);
+            let reflectance = microfacet::fresnel_dielectric(cos_theta, eta);
+            let direction = if reflectance > utils::random_double(rng) {
+                vec3::reflect(unit_direction, rec.normal())
+            } else {
+                vec3::refract(unit_direction, rec.normal(), 1.0 / eta)
+            };
+            srec.set_attenuation(color::Color::new(1.0, 1.0, 1.0));
+            srec.set_skip_pdf_ray(ray::Ray::new_with_time(rec.p(), direction, r_in.time()));
+            return true;
+        }
+        let frame = microfacet::shading_frame(rec.normal());
+        let wo = frame.inverse_transform(-unit_direction);
+        let lobe_choice = utils::random_double(rng);
+        srec.set_pdf(Box::new(microfacet::DielectricPdf::new(
+            frame,
+            wo,
+            self.ggx,
+            eta,
+            lobe_choice,
+        )));
+        true
+    }
+
+    fn scattering(
+        &self,
+        r_in: ray::Ray,
+        rec: &hittable::HitRecord,
+        _srec: &ScatterRecord,
+        scattered: ray::Ray,
+    ) -> color::Color {
+        let frame = microfacet::shading_frame(rec.normal());
+        let wo = frame.inverse_transform(-vec3::unit_vector(r_in.direction()));
+        let wi = frame.inverse_transform(vec3::unit_vector(scattered.direction()));
+        let f = microfacet::dielectric_bsdf(&self.ggx, wo, wi, self.relative_index(rec));
+        color::Color::new(f, f, f)
+    }
+}
+
+pub struct DiffuseLight {
+    tex: Arc<dyn texture::Texture>,
+}
+
+impl DiffuseLight {
+    pub fn from_texture(tex: Arc<dyn texture::Texture>) -> Self {
+        Self { tex }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(
+        &self,
+        _r_in: ray::Ray,
+        _rec: &mut hittable::HitRecord,
+        _srec: &mut ScatterRecord,
+        _rng: &mut utils::Rng,
+    ) -> bool {
+        false
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: vec3::Point3) -> color::Color {
+        self.tex.value(u, v, p)
+    }
+}
+
+// Scatters evenly in every direction; the phase function of a ConstantMedium.
+pub struct Isotropic {
+    tex: Arc<dyn texture::Texture>,
+}
+
+impl Isotropic {
+    pub fn from_texture(tex: Arc<dyn texture::Texture>) -> Self {
+        Self { tex }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(
+        &self,
+        _r_in: ray::Ray,
+        rec: &mut hittable::HitRecord,
+        srec: &mut ScatterRecord,
+        _rng: &mut utils::Rng,
+    ) -> bool {
+        let (u, v) = rec.uv();
+        srec.set_attenuation(self.tex.value(u, v, rec.p()));
+        srec.set_pdf(Box::new(pdf::SpherePdf));
+        true
+    }
+
+    fn scattering_pdf(
+        &self,
+        _r_in: ray::Ray,
+        _rec: &hittable::HitRecord,
+        _scattered: ray::Ray,
+    ) -> f64 {
+        1.0 / (4.0 * utils::PI)
+    }
+}
+
+// One material for artists in the style of Disney's principled BSDF: a base color blended
+// between a diffuse dielectric, a metal and rough glass by metallic and transmission, with a
+// clearcoat layer, sheen at grazing angles and emission. Every parameter is a texture; scalar
+// parameters use the average of its channels.
+pub struct Principled {
+    base_color: Arc<dyn texture::Texture>,
+    metallic: Arc<dyn texture::Texture>,
+    roughness: Arc<dyn texture::Texture>,
+    specular: Arc<dyn texture::Texture>,
+    clearcoat: Arc<dyn texture::Texture>,
+    clearcoat_roughness: Arc<dyn texture::Texture>,
+    sheen: Arc<dyn texture::Texture>,
+    sheen_tint: Arc<dyn texture::Texture>,
+    transmission: Arc<dyn texture::Texture>,
+    refraction_index: Arc<dyn texture::Texture>,
+    emission: Arc<dyn texture::Texture>,
+}
+
+// The parameters evaluated at one hit point.
+struct PrincipledPoint {
+    base_color: color::Color,
+    metallic: f64,
+    roughness: f64,
+    specular: f64,
+    clearcoat: f64,
+    clearcoat_roughness: f64,
+    sheen: f64,
+    sheen_tint: f64,
+    transmission: f64,
+    refraction_index: f64,
+}
+
+impl PrincipledPoint {
+    fn diffuse_weight(&self) -> f64 {
+        (1.0 - self.metallic) * (1.0 - self.transmission)
+    }
+
+    fn specular_weight(&self) -> f64 {
+        // The glass lobe has its own reflection, so the opaque specular lobe gives way to it.
+        1.0 - (1.0 - self.metallic) * self.transmission
+    }
+
+    fn glass_weight(&self) -> f64 {
+        (1.0 - self.metallic) * self.transmission
+    }
+
+    fn clearcoat_weight(&self) -> f64 {
+        // A quarter, as in Disney's model, so a full clearcoat is a thin varnish.
+        0.25 * self.clearcoat
+    }
+
+    fn specular_f0(&self) -> color::Color {
+        // Reflectance at normal incidence: specular 0.5 is 4%, like glass or plastic, and
+        // metals reflect their base color.
+        let dielectric = 0.08 * self.specular;
+        let d = color::Color::new(dielectric, dielectric, dielectric);
+        (1.0 - self.metallic) * d + self.metallic * self.base_color
+    }
+
+    fn sheen_color(&self) -> color::Color {
+        // Sheen tinted towards the hue of the base color.
+        let white = color::Color::new(1.0, 1.0, 1.0);
+        let lum = color::luminance(self.base_color);
+        let hue = if lum > 0.0 {
+            self.base_color / lum
+        } else {
+            white
+        };
+        self.sheen * ((1.0 - self.sheen_tint) * white + self.sheen_tint * hue)
+    }
+
+    fn lobe_weights(&self, wo: vec3::Vec3) -> [f64; 4] {
+        // Diffuse, specular, glass and clearcoat, roughly in proportion to how much light each
+        // reflects.
+        [
+            self.diffuse_weight() * (color::luminance(self.base_color) + self.sheen),
+            self.specular_weight()
+                * color::luminance(microfacet::fresnel_schlick(wo.z(), self.specular_f0())),
+            self.glass_weight(),
+            self.clearcoat_weight()
+                * color::luminance(microfacet::fresnel_schlick(
+                    wo.z(),
+                    color::Color::new(0.04, 0.04, 0.04),
+                )),
+        ]
+    }
+
+    fn sampled_weights(&self, wo: vec3::Vec3) -> [f64; 4] {
+        // The lobe weights without the smooth lobes, which scatter into a single direction and
+        // so are never sampled from a pdf.
+        let [diffuse, specular, glass, coat] = self.lobe_weights(wo);
+        let smooth = self.specular_ggx().is_smooth();
+        [
+            diffuse,
+            if smooth { 0.0 } else { specular },
+            if smooth { 0.0 } else { glass },
+            if self.clearcoat_ggx().is_smooth() {
+                0.0
+            } else {
+                coat
+            },
+        ]
+    }
+
+    fn specular_ggx(&self) -> microfacet::Ggx {
+        microfacet::Ggx::new(self.roughness, 0.0)
+    }
+
+    fn clearcoat_ggx(&self) -> microfacet::Ggx {
+        microfacet::Ggx::new(self.clearcoat_roughness, 0.0)
+    }
+}
+
+impl Principled {
+    pub fn new(base_color: Arc<dyn texture::Texture>) -> Self {
+        // Everything but the base color starts as a plain, slightly glossy dielectric.
+        let value = |x: f64| -> Arc<dyn texture::Texture> {
+            Arc::new(texture::SolidColor::new(color::Color::new(x, x, x)))
+        };
+        Self {
+            base_color,
+            metallic: value(0.0),
+            roughness: value(0.5),
+            specular: value(0.5),
+            clearcoat: value(0.0),
+            clearcoat_roughness: value(0.1),
+            sheen: value(0.0),
+            sheen_tint: value(0.5),
+            transmission: value(0.0),
+            refraction_index: value(1.5),
+            emission: value(0.0),
+        }
+    }
+
+    pub fn set_metallic(&mut self, input: Arc<dyn texture::Texture>) {
+        self.metallic = input;
+    }
+
+    pub fn set_roughness(&mut self, input: Arc<dyn texture::Texture>) {
+        self.roughness = input;
+    }
+
+    pub fn set_specular(&mut self, input: Arc<dyn texture::Texture>) {
+        self.specular = input;
+    }
+
+    pub fn set_clearcoat(&mut self, input: Arc<dyn texture::Texture>) {
+        self.clearcoat = input;
+    }
+
+    pub fn set_clearcoat_roughness(&mut self, input: Arc<dyn texture::Texture>) {
+        self.clearcoat_roughness = input;
+    }
+
+    pub fn set_sheen(&mut self, input: Arc<dyn texture::Texture>) {
+        self.sheen = input;
+    }
+
+    pub fn set_sheen_tint(&mut self, input: Arc<dyn texture::Texture>) {
+        self.sheen_tint = input;
+    }
+
+    pub fn set_transmission(&mut self, input: Arc<dyn texture::Texture>) {
+        self.transmission = input;
+    }
+
+    pub fn set_refraction_index(&mut self, input: Arc<dyn texture::Texture>) {
+        self.refraction_index = input;
+    }
+
+    pub fn set_emission(&mut self, input: Arc<dyn texture::Texture>) {
+        self.emission = input;
+    }
+
+    fn at(&self, rec: &hittable::HitRecord) -> PrincipledPoint {
+        let (u, v) = rec.uv();
+        let p = rec.p();
+        let scalar = |tex: &Arc<dyn texture::Texture>| {
+            let c = tex.value(u, v, p);
+            (c.x() + c.y() + c.z()) / 3.0
+        };
+        let fraction = |tex: &Arc<dyn texture::Texture>| scalar(tex).clamp(0.0, 1.0);
+        PrincipledPoint {
+            base_color: self.base_color.value(u, v, p),
+            metallic: fraction(&self.metallic),
+            roughness: fraction(&self.roughness),
+            specular: fraction(&self.specular),
+            clearcoat: fraction(&self.clearcoat),
+            clearcoat_roughness: fraction(&self.clearcoat_roughness),
+            sheen: fraction(&self.sheen),
+            sheen_tint: fraction(&self.sheen_tint),
+            transmission: fraction(&self.transmission),
+            refraction_index: scalar(&self.refraction_index).max(1.0),
+        }
+    }
+}
+
+impl Material for Principled {
+    fn scatter(
+        &self,
+        r_in: ray::Ray,
+        rec: &mut hittable::HitRecord,
+        srec: &mut ScatterRecord,
+        rng: &mut utils::Rng,
+    ) -> bool {
+        let m = self.at(rec);
+        let frame = microfacet::shading_frame(rec.normal());
+        let wo = frame.inverse_transform(-vec3::unit_vector(r_in.direction()));
+        let eta = if rec.front_face() {
+            m.refraction_index
+        } else {
+            1.0 / m.refraction_index
+        };
+
+        // Each lobe is picked in proportion to its weight. Smooth lobes are followed directly
+        // and the others are sampled together with a pdf; either way the result is scaled by
+        // the odds of picking it, so the mix stays unbiased.
+        let weights = m.lobe_weights(wo);
+        let [diffuse, specular, glass, coat] = m.sampled_weights(wo);
+        let smooth = m.specular_ggx().is_smooth();
+        let total: f64 = weights.iter().sum();
+        let pick = utils::random_double(rng) * total;
+        let unit_direction = vec3::unit_vector(r_in.direction());
+        let reflected = vec3::reflect(unit_direction, rec.normal());
+        if smooth && pick < weights[1] {
+            let fresnel = microfacet::fresnel_schlick(wo.z(), m.specular_f0());
+            srec.set_attenuation(m.specular_weight() * fresnel * (total / weights[1]));
+            srec.set_skip_pdf_ray(ray::Ray::new_with_time(rec.p(), reflected, r_in.time()));
+            return true;
+        }
+        if smooth && (weights[1]..weights[1] + weights[2]).contains(&pick) {
+            // As in RoughDielectric, reflected or refracted by the Fresnel reflectance, and
+            // only the light passing through is tinted.
+            let reflectance = microfacet::fresnel_dielectric(wo.z(), eta);
+            let (direction, tint) = if reflectance > utils::random_double(rng) {
+                (reflected, color::Color::new(1.0, 1.0, 1.0))
+            } else {
+                let refracted = vec3::refract(unit_direction, rec.normal(), 1.0 / eta);
+                (refracted, m.base_color)
+            };
+            srec.set_attenuation(m.glass_weight() * tint * (total / weights[2]));
+            srec.set_skip_pdf_ray(ray::Ray::new_with_time(rec.p(), direction, r_in.time()));
+            return true;
+        }
+        if m.clearcoat_ggx().is_smooth() && pick >= total - weights[3] {
+            let fresnel = microfacet::fresnel_schlick(wo.z(), color::Color::new(0.04, 0.04, 0.04));
+            srec.set_attenuation(m.clearcoat_weight() * fresnel * (total / weights[3]));
+            srec.set_skip_pdf_ray(ray::Ray::new_with_time(rec.p(), reflected, r_in.time()));
+            return true;
+        }
+
+        let lobes: Vec<(f64, Box<dyn pdf::Pdf>)> = vec![
+            (diffuse, Box::new(pdf::CosinePdf::new(rec.normal()))),
+            (
+                specular,
+                Box::new(microfacet::ReflectionPdf::new(frame, wo, m.specular_ggx())),
+            ),
+            (
+                glass,
+                Box::new(microfacet::DielectricPdf::new(
+                    frame,
+                    wo,
+                    m.specular_ggx(),
+                    eta,
+                    utils::random_double(rng),
+                )),
+            ),
+            (
+                coat,
+                Box::new(microfacet::ReflectionPdf::new(frame, wo, m.clearcoat_ggx())),
+            ),
+        ];
+        let lobes = pdf::WeightedPdf::new(lobes);
+        if lobes.is_empty() {
+            return false;
+        }
+        srec.set_pdf(Box::new(lobes));
         true
     }
+
+    fn scattering(
+        &self,
+        r_in: ray::Ray,
+        rec: &hittable::HitRecord,
+        _srec: &ScatterRecord,
+        scattered: ray::Ray,
+    ) -> color::Color {
+        let m = self.at(rec);
+        let frame = microfacet::shading_frame(rec.normal());
+        let wo = frame.inverse_transform(-vec3::unit_vector(r_in.direction()));
+        let wi = frame.inverse_transform(vec3::unit_vector(scattered.direction()));
+        let smooth = m.specular_ggx().is_smooth();
+        let mut f = color::Color::default();
+
+        if wo.z() > 0.0 && wi.z() > 0.0 {
+            // Burley's diffuse, with its retro-reflection at grazing angles on rough surfaces,
+            // and sheen.
+            let h = vec3::unit_vector(wo + wi);
+            let cos_d = vec3::dot(wi, h);
+            let fd90 = 0.5 + 2.0 * m.roughness * cos_d * cos_d;
+            let lobe = |cos: f64| 1.0 + (fd90 - 1.0) * (1.0 - cos).powi(5);
+            let diffuse = m.base_color * (lobe(wo.z()) * lobe(wi.z()) / utils::PI);
+            let sheen = m.sheen_color() * (1.0 - cos_d).powi(5);
+            f += m.diffuse_weight() * wi.z() * (diffuse + sheen);
+
+            if let Some((wm, spec)) =
+                microfacet::specular_bsdf(&m.specular_ggx(), wo, wi).filter(|_| !smooth)
+            {
+                let fresnel = microfacet::fresnel_schlick(vec3::dot(wo, wm), m.specular_f0());
+                f += m.specular_weight() * spec * fresnel;
+            }
+            if let Some((wm, coat)) = microfacet::specular_bsdf(&m.clearcoat_ggx(), wo, wi)
+                .filter(|_| !m.clearcoat_ggx().is_smooth())
+            {
+                let fresnel = microfacet::fresnel_schlick(
+                    vec3::dot(wo, wm),
+                    color::Color::new(0.04, 0.04, 0.04),
+                );
+                f += m.clearcoat_weight() * coat * fresnel;
+            }
+        }
+
+        if m.glass_weight() > 0.0 && !smooth {
+            let eta = if rec.front_face() {
+                m.refraction_index
+            } else {
+                1.0 / m.refraction_index
+            };
+            let glass = microfacet::dielectric_bsdf(&m.specular_ggx(), wo, wi, eta);
+            // Light passing through is tinted by the base color; its reflection isn't.
+            let tint = if wi.z() < 0.0 {
+                m.base_color
+            } else {
+                color::Color::new(1.0, 1.0, 1.0)
+            };
+            f += m.glass_weight() * glass * tint;
+        }
+
+        // Smooth lobes were left out of the pdf, so the lobes sampled with it were picked only
+        // some of the time.
+        let sampled: f64 = m.sampled_weights(wo).iter().sum();
+        f * (m.lobe_weights(wo).iter().sum::<f64>() / sampled)
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: vec3::Point3) -> color::Color {
+        self.emission.value(u, v, p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(x: f64) -> Arc<dyn texture::Texture> {
+        Arc::new(texture::SolidColor::new(color::Color::new(x, x, x)))
+    }
+
+    fn principled(roughness: f64) -> Principled {
+        let mut mat = Principled::new(Arc::new(texture::SolidColor::new(color::Color::new(
+            0.8, 0.3, 0.1,
+        ))));
+        mat.set_roughness(value(roughness));
+        mat.set_clearcoat(value(0.5));
+        mat.set_clearcoat_roughness(value(roughness));
+        mat
+    }
+
+    // A ray coming down at 45 degrees onto an upward-facing surface at the origin.
+    fn incoming() -> (ray::Ray, hittable::HitRecord) {
+        let r_in = ray::Ray::new(
+            vec3::Point3::new(-1.0, 1.0, 0.0),
+            vec3::Vec3::new(1.0, -1.0, 0.0),
+        );
+        let mut rec = hittable::HitRecord::default();
+        rec.set_face_normal(r_in, vec3::Vec3::new(0.0, 1.0, 0.0));
+        (r_in, rec)
+    }
+
+    // Estimates the fraction of light the material reflects or transmits from the incoming ray.
+    fn albedo(mat: &dyn Material, n: usize) -> color::Color {
+        let (r_in, mut rec) = incoming();
+        let mut rng = utils::Rng::new(3);
+        let mut sum = color::Color::default();
+        for _ in 0..n {
+            let mut srec = ScatterRecord::default();
+            if !mat.scatter(r_in, &mut rec, &mut srec, &mut rng) {
+                continue;
+            }
+            match srec.pdf() {
+                None => sum += srec.attenuation(),
+                Some(pdf) => {
+                    let u = (
+                        utils::random_double(&mut rng),
+                        utils::random_double(&mut rng),
+                    );
+                    let scattered = ray::Ray::new(rec.p(), pdf.generate(u));
+                    let pdf_value = pdf.value(scattered.direction());
+                    if pdf_value > 0.0 {
+                        sum += mat.scattering(r_in, &rec, &srec, scattered) / pdf_value;
+                    }
+                }
+            }
+        }
+        sum / n as f64
+    }
+
+    #[test]
+    fn smooth_principled_metal_is_a_mirror() {
+        let base = color::Color::new(0.9, 0.6, 0.3);
+        let mut mat = Principled::new(Arc::new(texture::SolidColor::new(base)));
+        mat.set_metallic(value(1.0));
+        mat.set_roughness(value(0.0));
+        let (r_in, mut rec) = incoming();
+        let mut srec = ScatterRecord::default();
+        assert!(mat.scatter(r_in, &mut rec, &mut srec, &mut utils::Rng::new(1)));
+        assert!(srec.pdf().is_none());
+        let direction = vec3::unit_vector(srec.skip_pdf_ray().direction());
+        assert!((direction - vec3::unit_vector(vec3::Vec3::new(1.0, 1.0, 0.0))).length() < 1e-12);
+        let expected = microfacet::fresnel_schlick(0.5_f64.sqrt(), base);
+        assert!((srec.attenuation() - expected).length() < 1e-12);
+    }
+
+    #[test]
+    fn smooth_principled_matches_nearly_smooth() {
+        // Whether the lobes are followed directly or sampled, a smooth material and a slightly
+        // rough one should let through nearly the same amount of light.
+        for transmission in [0.0, 1.0] {
+            let mut smooth = principled(0.0);
+            let mut rough = principled(0.1);
+            smooth.set_transmission(value(transmission));
+            rough.set_transmission(value(transmission));
+            let a = albedo(&smooth, 100_000);
+            let b = albedo(&rough, 100_000);
+            for (a, b) in [(a.x(), b.x()), (a.y(), b.y()), (a.z(), b.z())] {
+                assert!(
+                    (a - b).abs() < 0.02 * b,
+                    "{a} vs {b} at transmission {transmission}"
+                );
+            }
+        }
+    }
 }
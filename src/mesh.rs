@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use crate::{aabb, bvh, hittable, hittable_list, interval, material, ray, triangle, vec3};
+
+// A triangle mesh: every face references one shared vertex buffer, and the faces are kept in
+// their own BVH so the mesh is a single object to the rest of the scene.
+pub struct Mesh {
+    faces: bvh::BvhNode,
+    triangles: Vec<Arc<triangle::Triangle>>, // The same faces, for sampling the mesh as a light
+    area_cdf: Vec<f64>,                      // Running total of face areas
+}
+
+impl Mesh {
+    pub fn new(
+        vertices: Arc<triangle::VertexBuffer>,
+        indices: &[[usize; 3]],
+        mat: Arc<dyn material::Material>,
+    ) -> Self {
+        assert!(!indices.is_empty(), "a mesh needs at least one face");
+        let mut faces = hittable_list::HittableList::new();
+        let mut triangles = Vec::with_capacity(indices.len());
+        let mut area_cdf = Vec::with_capacity(indices.len());
+        let mut total_area = 0.0;
+        for face in indices {
+            assert!(
+                face.iter().all(|&i| i < vertices.len()),
+                "mesh face {:?} references a missing vertex",
+                face
+            );
+            let tri = Arc::new(triangle::Triangle::from_buffer(
+                vertices.clone(),
+                *face,
+                mat.clone(),
+            ));
+            total_area += tri.area();
+            area_cdf.push(total_area);
+            triangles.push(tri.clone());
+            faces.add(tri);
+        }
+        Self {
+            faces: bvh::BvhNode::new(&faces),
+            triangles,
+            area_cdf,
+        }
+    }
+}
+
+impl hittable::Hittable for Mesh {
+    fn hit(&self, r: ray::Ray, ray_t: interval::Interval, rec: &mut hittable::HitRecord) -> bool {
+        self.faces.hit(r, ray_t, rec)
+    }
+
+    fn bounding_box(&self) -> aabb::Aabb {
+        self.faces.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: vec3::Point3, direction: vec3::Vec3) -> f64 {
+        // random() picks faces in proportion to their area, and a direction can pass through
+        // several faces, so every face contributes. This is linear in the face count, which is
+        // fine for the small meshes lights are made of.
+        let total_area = self.area_cdf.last().copied().unwrap_or(0.0);
+        if total_area <= 0.0 {
+            return 0.0;
+        }
+        self.triangles
+            .iter()
+            .map(|tri| tri.area() / total_area * tri.pdf_value(origin, direction))
+            .sum()
+    }
+
+    fn random(&self, origin: vec3::Point3, (u1, u2): (f64, f64)) -> vec3::Vec3 {
+        // The first coordinate picks a face and what is left of it is reused for the face.
+        let total_area = self.area_cdf.last().copied().unwrap_or(0.0);
+        let target = u1 * total_area;
+        let i = self
+            .area_cdf
+            .partition_point(|&a| a <= target)
+            .min(self.triangles.len() - 1);
+        let start = if i == 0 { 0.0 } else { self.area_cdf[i - 1] };
+        let width = self.area_cdf[i] - start;
+        let remapped = if width > 0.0 {
+            ((target - start) / width).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        self.triangles[i].random(origin, (remapped, u2))
+    }
+}
@@ -0,0 +1,430 @@
+use std::ops::{Add, Div, Mul, Sub};
+
+use crate::{color, onb, pdf, utils, vec3};
+
+// GGX (Trowbridge-Reitz) microfacet scattering, after Walter et al. 2007 and Heitz 2018. All
+// directions here are in a shading frame with the normal along +z and both the incoming
+// direction wo and the scattered direction wi pointing away from the surface; wo is always on
+// the +z side because HitRecord normals face the incoming ray.
+//
+// The models are single scattering: light that would bounce between microfacets more than once
+// is dropped rather than invented, so rough surfaces come out slightly dark but never reflect
+// more than they receive.
+
+// Below this roughness the lobes are narrower than anything sampling can resolve, and the
+// materials switch to perfectly smooth reflection and refraction.
+const SMOOTH_ALPHA: f64 = 1e-3;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Ggx {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl Ggx {
+    pub fn new(roughness: f64, anisotropy: f64) -> Self {
+        // Perceptual roughness is squared into alpha. Anisotropy stretches the lobe along the
+        // tangent and narrows it across, as in Burley's Disney BRDF.
+        let alpha = roughness.clamp(0.0, 1.0).powi(2);
+        let aspect = (1.0 - 0.9 * anisotropy.clamp(0.0, 1.0)).sqrt();
+        Self {
+            alpha_x: (alpha / aspect).max(1e-4),
+            alpha_y: (alpha * aspect).max(1e-4),
+        }
+    }
+
+    pub fn is_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < SMOOTH_ALPHA
+    }
+
+    fn d(&self, wm: vec3::Vec3) -> f64 {
+        // Density of microfacet normals wm, normalised so its projection onto the macro surface
+        // integrates to one.
+        let cos2 = wm.z() * wm.z();
+        if cos2 < 1e-8 {
+            return 0.0;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        let (cos_phi, sin_phi) = azimuth(wm);
+        let e = tan2 * ((cos_phi / self.alpha_x).powi(2) + (sin_phi / self.alpha_y).powi(2));
+        1.0 / (utils::PI * self.alpha_x * self.alpha_y * cos2 * cos2 * (1.0 + e).powi(2))
+    }
+
+    fn lambda(&self, w: vec3::Vec3) -> f64 {
+        let cos2 = w.z() * w.z();
+        if cos2 < 1e-12 {
+            return f64::INFINITY;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        let (cos_phi, sin_phi) = azimuth(w);
+        let alpha2 = (cos_phi * self.alpha_x).powi(2) + (sin_phi * self.alpha_y).powi(2);
+        ((1.0 + alpha2 * tan2).sqrt() - 1.0) / 2.0
+    }
+
+    fn g1(&self, w: vec3::Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    fn g(&self, wo: vec3::Vec3, wi: vec3::Vec3) -> f64 {
+        // Height-correlated masking and shadowing.
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    fn visible_d(&self, w: vec3::Vec3, wm: vec3::Vec3) -> f64 {
+        // Density of the microfacet normals seen from direction w.
+        let cos = w.z().abs();
+        if cos < 1e-12 {
+            return 0.0;
+        }
+        self.g1(w) / cos * self.d(wm) * vec3::dot(w, wm).max(0.0)
+    }
+
+    fn sample_visible_normal(&self, w: vec3::Vec3, (u1, u2): (f64, f64)) -> vec3::Vec3 {
+        // Heitz's visible normal sampling: stretch the view into the hemisphere configuration,
+        // sample the projected disk there and unstretch the normal.
+        let mut wh = vec3::unit_vector(vec3::Vec3::new(
+            self.alpha_x * w.x(),
+            self.alpha_y * w.y(),
+            w.z(),
+        ));
+        if wh.z() < 0.0 {
+            wh = -wh;
+        }
+        let t1 = if wh.z() < 0.99999 {
+            vec3::unit_vector(vec3::cross(vec3::Vec3::new(0.0, 0.0, 1.0), wh))
+        } else {
+            vec3::Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vec3::cross(wh, t1);
+
+        let r = u1.sqrt();
+        let phi = 2.0 * utils::PI * u2;
+        let (px, mut py) = (r * phi.cos(), r * phi.sin());
+        let h = (1.0 - px * px).max(0.0).sqrt();
+        let s = (1.0 + wh.z()) / 2.0;
+        py = (1.0 - s) * h + s * py;
+        let pz = (1.0 - px * px - py * py).max(0.0).sqrt();
+        let nh = px * t1 + py * t2 + pz * wh;
+
+        vec3::unit_vector(vec3::Vec3::new(
+            self.alpha_x * nh.x(),
+            self.alpha_y * nh.y(),
+            nh.z().max(1e-6),
+        ))
+    }
+}
+
+fn azimuth(w: vec3::Vec3) -> (f64, f64) {
+    let sin_theta = (w.x() * w.x() + w.y() * w.y()).sqrt();
+    if sin_theta == 0.0 {
+        return (1.0, 0.0);
+    }
+    (
+        (w.x() / sin_theta).clamp(-1.0, 1.0),
+        (w.y() / sin_theta).clamp(-1.0, 1.0),
+    )
+}
+
+pub fn shading_frame(n: vec3::Vec3) -> onb::Onb {
+    // Anisotropic highlights follow the tangent, which runs around the world y axis like the
+    // lines of latitude on a globe. Only at the poles, where that is undefined, does it fall
+    // back to an arbitrary one.
+    let t = vec3::cross(vec3::Vec3::new(0.0, 1.0, 0.0), n);
+    if t.length_squared() < 1e-8 {
+        return onb::Onb::new(n);
+    }
+    onb::Onb::with_tangent(n, t)
+}
+
+pub fn reflect(wo: vec3::Vec3, wm: vec3::Vec3) -> vec3::Vec3 {
+    -wo + 2.0 * vec3::dot(wo, wm) * wm
+}
+
+pub fn refract(wo: vec3::Vec3, wm: vec3::Vec3, eta: f64) -> Option<vec3::Vec3> {
+    // Refracts wo through a facet with normal wm on its side, into a medium whose index relative
+    // to wo's is eta. None on total internal reflection.
+    let cos_i = vec3::dot(wm, wo);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-wo / eta + (cos_i / eta - cos_t) * wm)
+}
+
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    // Unpolarised Fresnel reflectance of a dielectric interface, where eta is the index on the
+    // far side relative to the near side.
+    let (cos_i, eta) = if cos_i < 0.0 {
+        (-cos_i, 1.0 / eta)
+    } else {
+        (cos_i, eta)
+    };
+    let cos_i = cos_i.min(1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_parl = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perp = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_parl * r_parl + r_perp * r_perp) / 2.0
+}
+
+pub fn fresnel_conductor(cos_i: f64, eta: color::Color, k: color::Color) -> color::Color {
+    // Fresnel reflectance of a conductor with complex index eta + ik, per channel.
+    let channel = |eta: f64, k: f64| {
+        let cos_i = cos_i.clamp(0.0, 1.0);
+        let eta = Complex::new(eta, k);
+        let sin2_i = 1.0 - cos_i * cos_i;
+        let sin2_t = Complex::new(sin2_i, 0.0) / (eta * eta);
+        let cos_t = (Complex::new(1.0, 0.0) - sin2_t).sqrt();
+        let cos_i = Complex::new(cos_i, 0.0);
+        let r_parl = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+        let r_perp = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+        (r_parl.norm() + r_perp.norm()) / 2.0
+    };
+    color::Color::new(
+        channel(eta.x(), k.x()),
+        channel(eta.y(), k.y()),
+        channel(eta.z(), k.z()),
+    )
+}
+
+pub fn conductor_bsdf(
+    ggx: &Ggx,
+    wo: vec3::Vec3,
+    wi: vec3::Vec3,
+    eta: color::Color,
+    k: color::Color,
+) -> color::Color {
+    // The BRDF times the cosine term.
+    match specular_bsdf(ggx, wo, wi) {
+        Some((wm, f)) => fresnel_conductor(vec3::dot(wo, wm), eta, k) * f,
+        None => color::Color::default(),
+    }
+}
+
+pub fn specular_bsdf(ggx: &Ggx, wo: vec3::Vec3, wi: vec3::Vec3) -> Option<(vec3::Vec3, f64)> {
+    // The facet normal between wo and wi, and the BRDF times the cosine term of a reflection
+    // off it without the Fresnel factor, for the caller to supply.
+    if wo.z() <= 0.0 || wi.z() <= 0.0 {
+        return None;
+    }
+    let wm = wo + wi;
+    if wm.near_zero() {
+        return None;
+    }
+    let wm = vec3::unit_vector(wm);
+    Some((wm, ggx.d(wm) * ggx.g(wo, wi) / (4.0 * wo.z())))
+}
+
+pub fn fresnel_schlick(cos_i: f64, f0: color::Color) -> color::Color {
+    let white = color::Color::new(1.0, 1.0, 1.0);
+    f0 + (1.0 - cos_i.clamp(0.0, 1.0)).powi(5) * (white - f0)
+}
+
+pub fn dielectric_bsdf(ggx: &Ggx, wo: vec3::Vec3, wi: vec3::Vec3, eta: f64) -> f64 {
+    // The BSDF times the cosine term, reflecting when wi is on wo's side and transmitting
+    // otherwise. Like Dielectric, transmitted radiance isn't scaled by 1/eta^2: that keeps
+    // the reflected and transmitted fractions summing to at most one, and the factors cancel
+    // for light that leaves a closed object again.
+    let (cos_o, cos_i) = (wo.z(), wi.z());
+    if cos_o <= 0.0 || cos_i == 0.0 {
+        return 0.0;
+    }
+    let reflect = cos_i > 0.0;
+    let etap = if reflect { 1.0 } else { eta };
+    let wm = wi * etap + wo;
+    if wm.near_zero() {
+        return 0.0;
+    }
+    let mut wm = vec3::unit_vector(wm);
+    if wm.z() < 0.0 {
+        wm = -wm;
+    }
+    // Discard microfacets facing away from either direction.
+    if vec3::dot(wm, wi) * cos_i < 0.0 || vec3::dot(wm, wo) < 0.0 {
+        return 0.0;
+    }
+
+    let fresnel = fresnel_dielectric(vec3::dot(wo, wm), eta);
+    if reflect {
+        fresnel * ggx.d(wm) * ggx.g(wo, wi) / (4.0 * cos_o)
+    } else {
+        let denom = (vec3::dot(wi, wm) + vec3::dot(wo, wm) / etap).powi(2) * cos_o;
+        ggx.d(wm) * (1.0 - fresnel) * ggx.g(wo, wi) * (vec3::dot(wi, wm) * vec3::dot(wo, wm)).abs()
+            / denom
+    }
+}
+
+// Reflections off visible microfacet normals, for a rough conductor or a glossy coating.
+pub struct ReflectionPdf {
+    frame: onb::Onb,
+    wo: vec3::Vec3,
+    ggx: Ggx,
+}
+
+impl ReflectionPdf {
+    pub fn new(frame: onb::Onb, wo: vec3::Vec3, ggx: Ggx) -> Self {
+        Self { frame, wo, ggx }
+    }
+}
+
+impl pdf::Pdf for ReflectionPdf {
+    fn value(&self, direction: vec3::Vec3) -> f64 {
+        // Directions are generated below the surface too, when a facet reflects into it; they
+        // count here so value() matches generate() everywhere.
+        let wi = self.frame.inverse_transform(vec3::unit_vector(direction));
+        reflection(&self.ggx, self.wo, wi).map_or(0.0, |(_, pdf)| pdf)
+    }
+
+    fn generate(&self, u: (f64, f64)) -> vec3::Vec3 {
+        let wm = self.ggx.sample_visible_normal(self.wo, u);
+        self.frame.transform(reflect(self.wo, wm))
+    }
+}
+
+// Reflection or refraction off visible microfacet normals, for rough glass. Which of the two
+// follows the facet's Fresnel reflectance, decided by lobe_choice drawn when the pdf was made.
+pub struct DielectricPdf {
+    frame: onb::Onb,
+    wo: vec3::Vec3,
+    ggx: Ggx,
+    eta: f64,
+    lobe_choice: f64,
+}
+
+impl DielectricPdf {
+    pub fn new(frame: onb::Onb, wo: vec3::Vec3, ggx: Ggx, eta: f64, lobe_choice: f64) -> Self {
+        Self {
+            frame,
+            wo,
+            ggx,
+            eta,
+            lobe_choice,
+        }
+    }
+}
+
+impl pdf::Pdf for DielectricPdf {
+    fn value(&self, direction: vec3::Vec3) -> f64 {
+        // The density from both ways generate() could have reached the direction.
+        let wi = self.frame.inverse_transform(vec3::unit_vector(direction));
+        // Reflection is chosen with probability equal to the facet's Fresnel reflectance.
+        let reflected = reflection(&self.ggx, self.wo, wi).map_or(0.0, |(wm, pdf)| {
+            fresnel_dielectric(vec3::dot(self.wo, wm), self.eta) * pdf
+        });
+
+        let wm = self.wo + self.eta * wi;
+        if wm.near_zero() {
+            return reflected;
+        }
+        let mut wm = vec3::unit_vector(wm);
+        if wm.z() < 0.0 {
+            wm = -wm;
+        }
+        let (wi_wm, wo_wm) = (vec3::dot(wi, wm), vec3::dot(self.wo, wm));
+        if wo_wm <= 0.0 || wi_wm >= 0.0 {
+            return reflected;
+        }
+        let transmit = 1.0 - fresnel_dielectric(wo_wm, self.eta);
+        let dwm_dwi = wi_wm.abs() / (wi_wm + wo_wm / self.eta).powi(2);
+        reflected + transmit * self.ggx.visible_d(self.wo, wm) * dwm_dwi
+    }
+
+    fn generate(&self, u: (f64, f64)) -> vec3::Vec3 {
+        let wm = self.ggx.sample_visible_normal(self.wo, u);
+        let reflectance = fresnel_dielectric(vec3::dot(self.wo, wm), self.eta);
+        let wi = if self.lobe_choice < reflectance {
+            reflect(self.wo, wm)
+        } else {
+            refract(self.wo, wm, self.eta).unwrap_or_else(|| reflect(self.wo, wm))
+        };
+        self.frame.transform(wi)
+    }
+}
+
+fn reflection(ggx: &Ggx, wo: vec3::Vec3, wi: vec3::Vec3) -> Option<(vec3::Vec3, f64)> {
+    // The facet normal that reflects wo into wi, if it is visible from wo, and the density of
+    // sampling wi by reflecting off a visible facet.
+    let wm = wo + wi;
+    if wm.near_zero() {
+        return None;
+    }
+    let mut wm = vec3::unit_vector(wm);
+    if wm.z() < 0.0 {
+        wm = -wm;
+    }
+    let wo_wm = vec3::dot(wo, wm);
+    if wo_wm <= 0.0 {
+        return None;
+    }
+    Some((wm, ggx.visible_d(wo, wm) / (4.0 * wo_wm)))
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    fn norm(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    fn sqrt(self) -> Self {
+        // Principal square root.
+        let n = self.norm().sqrt();
+        if n == 0.0 {
+            return Complex::new(0.0, 0.0);
+        }
+        let t1 = (0.5 * (n + self.re.abs())).sqrt();
+        let t2 = 0.5 * self.im / t1;
+        if self.re >= 0.0 {
+            Complex::new(t1, t2)
+        } else {
+            Complex::new(t2.abs(), t1.copysign(self.im))
+        }
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, rhs: Complex) -> Complex {
+        Complex::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, rhs: Complex) -> Complex {
+        Complex::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, rhs: Complex) -> Complex {
+        Complex::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+    fn div(self, rhs: Complex) -> Complex {
+        let scale = 1.0 / rhs.norm();
+        Complex::new(
+            scale * (self.re * rhs.re + self.im * rhs.im),
+            scale * (self.im * rhs.re - self.re * rhs.im),
+        )
+    }
+}
@@ -0,0 +1,442 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::{error, fmt, fs, io};
+
+use crate::{color, hittable_list, material, mesh, triangle, vec3};
+
+// Wavefront OBJ import. Faces are fan-triangulated and split into one mesh per group and
+// material, with positions, normals and texture coordinates de-indexed into a vertex buffer per
+// mesh. Materials come from any `mtllib` files and map onto the existing materials:
+//
+// - transparent (`d` < 1, `Tr` > 0) or refractive `illum` models become Dielectric with `Ni`
+// - a specular color (`Ks`) brighter than the diffuse one, or `illum 3`, becomes Metal, with
+//   fuzz derived from the `Ns` exponent
+// - anything else is Lambertian with `Kd`
+//
+// Faces without a `usemtl`, or naming a material that was never defined, use the fallback. So
+// do faces whose `mtllib` is missing or unreadable, as exported models often ship without it.
+
+#[derive(Debug)]
+pub enum Error {
+    Io(String, io::Error),
+    Parse {
+        file: String,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(file, err) => write!(f, "could not read {}: {}", file, err),
+            Error::Parse {
+                file,
+                line,
+                message,
+            } => write!(f, "{} line {}: {}", file, line, message),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+// One face corner: 0-based indices of the position, texture coordinate and normal.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+// Faces are split into meshes by (group name, material name).
+type ChunkKey = (String, Option<String>);
+
+#[derive(Default)]
+struct ObjData {
+    positions: Vec<vec3::Point3>,
+    texcoords: Vec<(f64, f64)>,
+    normals: Vec<vec3::Vec3>,
+    chunks: Vec<(ChunkKey, Vec<[Corner; 3]>)>,
+    materials: HashMap<String, Arc<dyn material::Material>>,
+    libraries: Vec<PathBuf>, // MTL files referenced, whether or not they could be read
+}
+
+pub fn load(
+    path: &Path,
+    fallback: Arc<dyn material::Material>,
+    files: &mut Vec<PathBuf>,
+) -> Result<hittable_list::HittableList, Error> {
+    // The model and its material libraries are added to files.
+    let data = parse(path)?;
+    files.push(path.to_path_buf());
+    files.extend(data.libraries.iter().cloned());
+
+    let mut world = hittable_list::HittableList::new();
+    for ((_, mat_name), faces) in &data.chunks {
+        let mat = mat_name
+            .as_ref()
+            .and_then(|name| data.materials.get(name))
+            .cloned()
+            .unwrap_or_else(|| fallback.clone());
+        let (vertices, indices) = build_vertices(&data, faces);
+        world.add(Arc::new(mesh::Mesh::new(Arc::new(vertices), &indices, mat)));
+    }
+    Ok(world)
+}
+
+fn build_vertices(
+    data: &ObjData,
+    faces: &[[Corner; 3]],
+) -> (triangle::VertexBuffer, Vec<[usize; 3]>) {
+    // Normals and texture coordinates are only used when every corner of the mesh has them.
+    let all_normals = faces.iter().flatten().all(|c| c.2.is_some());
+    let all_texcoords = faces.iter().flatten().all(|c| c.1.is_some());
+
+    let mut remap: HashMap<Corner, usize> = HashMap::new();
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut texcoords = Vec::new();
+    let mut indices = Vec::with_capacity(faces.len());
+
+    for face in faces {
+        let tri = face.map(|corner| {
+            *remap.entry(corner).or_insert_with(|| {
+                positions.push(data.positions[corner.0]);
+                if all_normals {
+                    normals.push(data.normals[corner.2.unwrap()]);
+                }
+                if all_texcoords {
+                    texcoords.push(data.texcoords[corner.1.unwrap()]);
+                }
+                positions.len() - 1
+            })
+        });
+        indices.push(tri);
+    }
+
+    let mut vertices = triangle::VertexBuffer::new(positions, normals);
+    vertices.set_texcoords(texcoords);
+    (vertices, indices)
+}
+
+fn parse(path: &Path) -> Result<ObjData, Error> {
+    let file = path.display().to_string();
+    let src = fs::read_to_string(path).map_err(|err| Error::Io(file.clone(), err))?;
+    let err = |line: usize, message: String| Error::Parse {
+        file: file.clone(),
+        line,
+        message,
+    };
+
+    let mut data = ObjData::default();
+    let mut group = String::from("default");
+    let mut mat_name: Option<String> = None;
+
+    for (n, raw) in src.lines().enumerate() {
+        let line = n + 1;
+        let content = raw.split('#').next().unwrap_or("").trim();
+        let mut parts = content.split_whitespace();
+        let Some(keyword) = parts.next() else {
+            continue;
+        };
+        let args: Vec<&str> = parts.collect();
+
+        match keyword {
+            "v" => data
+                .positions
+                .push(parse_vec3(&args).map_err(|m| err(line, m))?),
+            "vn" => data
+                .normals
+                .push(parse_vec3(&args).map_err(|m| err(line, m))?),
+            "vt" => {
+                let u = parse_float(args.first().copied()).map_err(|m| err(line, m))?;
+                let v = match args.get(1) {
+                    Some(v) => parse_float(Some(v)).map_err(|m| err(line, m))?,
+                    None => 0.0,
+                };
+                data.texcoords.push((u, v));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(err(
+                        line,
+                        "a face needs at least three vertices".to_string(),
+                    ));
+                }
+                let corners = args
+                    .iter()
+                    .map(|c| parse_corner(c, &data))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|m| err(line, m))?;
+                let faces = current_chunk(&mut data, &group, &mat_name);
+                for i in 1..corners.len() - 1 {
+                    faces.push([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            "g" | "o" => {
+                group = if args.is_empty() {
+                    String::from("default")
+                } else {
+                    args.join(" ")
+                };
+            }
+            "usemtl" => mat_name = args.first().map(|s| s.to_string()),
+            "mtllib" => {
+                let dir = path.parent().unwrap_or(Path::new(""));
+                for lib in &args {
+                    data.libraries.push(dir.join(lib));
+                    match parse_mtl(&dir.join(lib), &mut data.materials) {
+                        Err(err @ Error::Io(..)) => {
+                            eprintln!("warning: {}; using the fallback material", err)
+                        }
+                        result => result?,
+                    }
+                }
+            }
+            // Smoothing groups, lines, points and free-form geometry aren't supported.
+            _ => {}
+        }
+    }
+
+    Ok(data)
+}
+
+fn current_chunk<'a>(
+    data: &'a mut ObjData,
+    group: &str,
+    mat_name: &Option<String>,
+) -> &'a mut Vec<[Corner; 3]> {
+    let key = (group.to_string(), mat_name.clone());
+    match data.chunks.iter().position(|(k, _)| *k == key) {
+        Some(i) => &mut data.chunks[i].1,
+        None => {
+            data.chunks.push((key, Vec::new()));
+            &mut data.chunks.last_mut().unwrap().1
+        }
+    }
+}
+
+fn parse_corner(s: &str, data: &ObjData) -> Result<Corner, String> {
+    // Corners are `v`, `v/vt`, `v//vn` or `v/vt/vn`, with 1-based or negative (relative) indices.
+    let mut parts = s.split('/');
+    let v = resolve_index(parts.next(), data.positions.len(), "position")?
+        .ok_or_else(|| format!("face corner `{}` has no position", s))?;
+    let vt = resolve_index(parts.next(), data.texcoords.len(), "texture coordinate")?;
+    let vn = resolve_index(parts.next(), data.normals.len(), "normal")?;
+    Ok((v, vt, vn))
+}
+
+fn resolve_index(s: Option<&str>, count: usize, what: &str) -> Result<Option<usize>, String> {
+    let s = match s {
+        Some(s) if !s.is_empty() => s,
+        _ => return Ok(None),
+    };
+    let i: i64 = s
+        .parse()
+        .map_err(|_| format!("invalid {} index `{}`", what, s))?;
+    let resolved = if i < 0 { count as i64 + i } else { i - 1 };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(format!("{} index {} is out of range", what, i));
+    }
+    Ok(Some(resolved as usize))
+}
+
+fn parse_float(s: Option<&str>) -> Result<f64, String> {
+    let s = s.ok_or_else(|| "missing number".to_string())?;
+    s.parse().map_err(|_| format!("invalid number `{}`", s))
+}
+
+fn parse_vec3(args: &[&str]) -> Result<vec3::Vec3, String> {
+    Ok(vec3::Vec3::new(
+        parse_float(args.first().copied())?,
+        parse_float(args.get(1).copied())?,
+        parse_float(args.get(2).copied())?,
+    ))
+}
+
+#[derive(Clone)]
+struct MtlDesc {
+    kd: color::Color,
+    ks: color::Color,
+    ns: f64,
+    ni: f64,
+    dissolve: f64,
+    illum: i64,
+}
+
+impl Default for MtlDesc {
+    fn default() -> Self {
+        Self {
+            kd: color::Color::new(0.8, 0.8, 0.8),
+            ks: color::Color::default(),
+            ns: 0.0,
+            ni: 1.5,
+            dissolve: 1.0,
+            illum: 2,
+        }
+    }
+}
+
+impl MtlDesc {
+    fn build(&self) -> Arc<dyn material::Material> {
+        let max = |c: color::Color| c.x().max(c.y()).max(c.z());
+        if self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9) {
+            Arc::new(material::Dielectric::new(self.ni))
+        } else if self.illum == 3 || max(self.ks) > max(self.kd) {
+            // Map the Phong exponent onto fuzz: a tight highlight is a sharp mirror.
+            let fuzz = (2.0 / (self.ns + 2.0)).sqrt();
+            Arc::new(material::Metal::new(self.ks, fuzz))
+        } else {
+            Arc::new(material::Lambertian::new(self.kd))
+        }
+    }
+}
+
+fn parse_mtl(
+    path: &Path,
+    materials: &mut HashMap<String, Arc<dyn material::Material>>,
+) -> Result<(), Error> {
+    let file = path.display().to_string();
+    let src = fs::read_to_string(path).map_err(|err| Error::Io(file.clone(), err))?;
+
+    let mut current: Option<(String, MtlDesc)> = None;
+    for (n, raw) in src.lines().enumerate() {
+        let content = raw.split('#').next().unwrap_or("").trim();
+        let mut parts = content.split_whitespace();
+        let Some(keyword) = parts.next() else {
+            continue;
+        };
+        let args: Vec<&str> = parts.collect();
+        let err = |message: String| Error::Parse {
+            file: file.clone(),
+            line: n + 1,
+            message,
+        };
+
+        if keyword == "newmtl" {
+            if let Some((name, desc)) = current.take() {
+                materials.insert(name, desc.build());
+            }
+            let name = args.join(" ");
+            if name.is_empty() {
+                return Err(err("newmtl needs a name".to_string()));
+            }
+            current = Some((name, MtlDesc::default()));
+            continue;
+        }
+
+        let Some((_, desc)) = current.as_mut() else {
+            continue;
+        };
+        match keyword {
+            "Kd" => desc.kd = parse_vec3(&args).map_err(err)?,
+            "Ks" => desc.ks = parse_vec3(&args).map_err(err)?,
+            "Ns" => desc.ns = parse_float(args.first().copied()).map_err(err)?,
+            "Ni" => desc.ni = parse_float(args.first().copied()).map_err(err)?,
+            "d" => desc.dissolve = parse_float(args.first().copied()).map_err(err)?,
+            "Tr" => desc.dissolve = 1.0 - parse_float(args.first().copied()).map_err(err)?,
+            "illum" => {
+                desc.illum = args
+                    .first()
+                    .and_then(|s| s.parse().ok())
+                    .ok_or_else(|| err("invalid illum model".to_string()))?
+            }
+            _ => {}
+        }
+    }
+    if let Some((name, desc)) = current {
+        materials.insert(name, desc.build());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::{hittable, interval, ray};
+
+    // Writes an OBJ file into a directory of its own and returns its path.
+    fn write_obj(name: &str, src: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("obj-test-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("model.obj");
+        fs::write(&path, src).unwrap();
+        path
+    }
+
+    fn parse_faces(name: &str, src: &str) -> Vec<[Corner; 3]> {
+        let path = write_obj(name, src);
+        let data = parse(&path);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        let data = data.unwrap();
+        data.chunks
+            .into_iter()
+            .flat_map(|(_, faces)| faces)
+            .collect()
+    }
+
+    const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+
+    #[test]
+    fn polygons_are_fan_triangulated() {
+        let faces = parse_faces("fan", &format!("{}v 0.5 1.5 0\nf 1 2 3 4 5\n", SQUARE));
+        let positions: Vec<[usize; 3]> = faces.iter().map(|f| f.map(|c| c.0)).collect();
+        assert_eq!(positions, [[0, 1, 2], [0, 2, 3], [0, 3, 4]]);
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_last_vertex() {
+        let src = format!("{}f -4 -3 -2\nv 2 2 0\nf -3 -2 -1\n", SQUARE);
+        let faces = parse_faces("negative", &src);
+        let positions: Vec<[usize; 3]> = faces.iter().map(|f| f.map(|c| c.0)).collect();
+        assert_eq!(positions, [[0, 1, 2], [2, 3, 4]]);
+    }
+
+    #[test]
+    fn corner_variants_are_parsed() {
+        let faces = "f 1 2 3\nf 1/1 2/2 3/3\nf 1//1 2//1 3//1\nf 1/1/1 2/2/1 3/3/1\n";
+        let src = format!("{}vt 0 0\nvt 1 0\nvt 1 1\nvn 0 0 1\n{}", SQUARE, faces);
+        let faces = parse_faces("variants", &src);
+        let first: Vec<Corner> = faces.iter().map(|f| f[1]).collect();
+        assert_eq!(
+            first,
+            [
+                (1, None, None),
+                (1, Some(1), None),
+                (1, None, Some(0)),
+                (1, Some(1), Some(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn out_of_range_index_reports_its_line() {
+        let path = write_obj("range", &format!("{}\nf 1 2 9\n", SQUARE));
+        let result = parse(&path);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        let err = result.err().unwrap().to_string();
+        assert!(
+            err.ends_with("line 6: position index 9 is out of range"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn missing_material_library_uses_the_fallback() {
+        let src = format!("mtllib missing.mtl\nusemtl red\n{}f 1 2 3 4\n", SQUARE);
+        let path = write_obj("missing-mtl", &src);
+        let fallback: Arc<dyn material::Material> = Arc::new(material::Lambertian::default());
+        let mut files = Vec::new();
+        let model = load(&path, fallback.clone(), &mut files);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        let model = model.unwrap();
+        assert_eq!(files, [path.clone(), path.with_file_name("missing.mtl")]);
+
+        let r = ray::Ray::new(
+            vec3::Point3::new(0.5, 0.5, 1.0),
+            vec3::Vec3::new(0.0, 0.0, -1.0),
+        );
+        let mut rec = hittable::HitRecord::default();
+        assert!(model.hit(r, interval::Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert!(Arc::ptr_eq(&rec.mat(), &fallback));
+    }
+}
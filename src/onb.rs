@@ -0,0 +1,48 @@
+use crate::vec3;
+
+// Orthonormal basis with w along a given direction, used to carry samples generated about +z
+// onto a surface normal or towards a light.
+#[derive(Debug, Copy, Clone)]
+pub struct Onb {
+    axis: [vec3::Vec3; 3],
+}
+
+impl Onb {
+    pub fn new(n: vec3::Vec3) -> Self {
+        let w = vec3::unit_vector(n);
+        let a = if w.x().abs() > 0.9 {
+            vec3::Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            vec3::Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = vec3::unit_vector(vec3::cross(w, a));
+        let u = vec3::cross(w, v);
+        Self { axis: [u, v, w] }
+    }
+
+    pub fn with_tangent(n: vec3::Vec3, t: vec3::Vec3) -> Self {
+        // A basis with w along n and u along t projected onto the plane perpendicular to n.
+        let w = vec3::unit_vector(n);
+        let u = vec3::unit_vector(t - vec3::dot(t, w) * w);
+        let v = vec3::cross(w, u);
+        Self { axis: [u, v, w] }
+    }
+
+    pub fn w(&self) -> vec3::Vec3 {
+        self.axis[2]
+    }
+
+    pub fn transform(&self, v: vec3::Vec3) -> vec3::Vec3 {
+        // Transform from basis coordinates to local space.
+        (v.x() * self.axis[0]) + (v.y() * self.axis[1]) + (v.z() * self.axis[2])
+    }
+
+    pub fn inverse_transform(&self, v: vec3::Vec3) -> vec3::Vec3 {
+        // Transform from local space to basis coordinates.
+        vec3::Vec3::new(
+            vec3::dot(v, self.axis[0]),
+            vec3::dot(v, self.axis[1]),
+            vec3::dot(v, self.axis[2]),
+        )
+    }
+}
@@ -0,0 +1,212 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::{color, framebuffer};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    PpmAscii,
+    PpmBinary,
+    Png,
+    Pfm,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> io::Result<Self> {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("ppm") => Ok(Format::PpmBinary),
+            Some("png") => Ok(Format::Png),
+            Some("pfm") => Ok(Format::Pfm),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported output extension: {}", path.display()),
+            )),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "p3" => Ok(Format::PpmAscii),
+            "p6" | "ppm" => Ok(Format::PpmBinary),
+            "png" => Ok(Format::Png),
+            "pfm" => Ok(Format::Pfm),
+            _ => Err(format!(
+                "unknown format `{}`, expected one of p3, p6, png, pfm",
+                s
+            )),
+        }
+    }
+}
+
+pub fn write_image(
+    image: &framebuffer::Framebuffer,
+    format: Format,
+    path: &Path,
+) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write_format(image, format, &mut out)?;
+    out.flush()
+}
+
+pub fn write_format(
+    image: &framebuffer::Framebuffer,
+    format: Format,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    match format {
+        Format::PpmAscii => write_ppm(image, out),
+        Format::PpmBinary => write_ppm_binary(image, out),
+        Format::Png => write_png(image, out),
+        Format::Pfm => write_pfm(image, out),
+    }
+}
+
+pub fn write_ppm(image: &framebuffer::Framebuffer, out: &mut impl Write) -> io::Result<()> {
+    // Plain-text P3 PPM: a header followed by one "r g b" line per pixel.
+    writeln!(out, "P3\n{0} {1}\n255\n", image.width(), image.height())?;
+    for &pixel_color in image.pixels() {
+        color::write_color(out, pixel_color)?;
+    }
+    Ok(())
+}
+
+pub fn write_ppm_binary(image: &framebuffer::Framebuffer, out: &mut impl Write) -> io::Result<()> {
+    // Raw P6 PPM: the same header as P3, then three bytes per pixel with no separators.
+    write!(out, "P6\n{0} {1}\n255\n", image.width(), image.height())?;
+    out.write_all(&to_rgb8(image))
+}
+
+pub fn write_png(image: &framebuffer::Framebuffer, out: &mut impl Write) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(out, image.width() as u32, image.height() as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    // Unlike the PPM writers, which keep the original gamma 2 curve, PNG is written as real
+    // sRGB and tagged so.
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    let data: Vec<u8> = image
+        .pixels()
+        .iter()
+        .flat_map(|&pixel_color| color::to_srgb_bytes(pixel_color))
+        .collect();
+    writer.write_image_data(&data).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
+
+pub fn write_pfm(image: &framebuffer::Framebuffer, out: &mut impl Write) -> io::Result<()> {
+    // Portable float map: linear radiance as little-endian f32 (negative scale), stored
+    // bottom row first. No gamma or clamping so highlights above 1.0 survive.
+    write!(out, "PF\n{0} {1}\n-1.0\n", image.width(), image.height())?;
+    let mut data = Vec::with_capacity(image.width() * image.height() * 12);
+    for row in image.pixels().chunks(image.width()).rev() {
+        for pixel_color in row {
+            for c in [pixel_color.x(), pixel_color.y(), pixel_color.z()] {
+                data.extend_from_slice(&(c as f32).to_le_bytes());
+            }
+        }
+    }
+    out.write_all(&data)
+}
+
+fn to_rgb8(image: &framebuffer::Framebuffer) -> Vec<u8> {
+    image
+        .pixels()
+        .iter()
+        .flat_map(|&pixel_color| color::to_bytes(pixel_color))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 2x2 image whose top row is black and white and whose bottom row is a quarter-bright
+    // red and blue.
+    fn test_image() -> framebuffer::Framebuffer {
+        let mut image = framebuffer::Framebuffer::new(2, 2);
+        image.row_mut(0).copy_from_slice(&[
+            color::Color::new(0.0, 0.0, 0.0),
+            color::Color::new(1.0, 1.0, 1.0),
+        ]);
+        image.row_mut(1).copy_from_slice(&[
+            color::Color::new(0.25, 0.0, 0.0),
+            color::Color::new(0.0, 0.0, 0.25),
+        ]);
+        image
+    }
+
+    fn encode(format: Format) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_format(&test_image(), format, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn p3_lists_gamma_encoded_pixels_top_row_first() {
+        let text = String::from_utf8(encode(Format::PpmAscii)).unwrap();
+        let lines: Vec<&str> = text.lines().filter(|l| !l.is_empty()).collect();
+        assert_eq!(
+            lines,
+            [
+                "P3",
+                "2 2",
+                "255",
+                "0 0 0",
+                "255 255 255",
+                "128 0 0",
+                "0 0 128"
+            ]
+        );
+    }
+
+    #[test]
+    fn p6_writes_three_bytes_per_pixel_after_the_header() {
+        let data = encode(Format::PpmBinary);
+        let header = b"P6\n2 2\n255\n";
+        assert_eq!(&data[..header.len()], header);
+        assert_eq!(
+            &data[header.len()..],
+            [0, 0, 0, 255, 255, 255, 128, 0, 0, 0, 0, 128]
+        );
+    }
+
+    #[test]
+    fn png_is_tagged_and_encoded_as_srgb() {
+        let mut image = framebuffer::Framebuffer::new(1, 1);
+        image.row_mut(0)[0] = color::Color::new(0.18, 0.18, 0.18);
+        let mut data = Vec::new();
+        write_png(&image, &mut data).unwrap();
+
+        let decoder = png::Decoder::new(io::Cursor::new(data));
+        let mut reader = decoder.read_info().unwrap();
+        assert!(reader.info().srgb.is_some());
+        let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut pixels).unwrap();
+        assert_eq!(pixels, [118, 118, 118]);
+    }
+
+    #[test]
+    fn pfm_writes_linear_floats_bottom_row_first() {
+        let data = encode(Format::Pfm);
+        let header = b"PF\n2 2\n-1.0\n";
+        assert_eq!(&data[..header.len()], header);
+        let values: Vec<f32> = data[header.len()..]
+            .chunks(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        assert_eq!(
+            values,
+            [0.25, 0.0, 0.0, 0.0, 0.0, 0.25, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0]
+        );
+    }
+}
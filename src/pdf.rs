@@ -0,0 +1,140 @@
+use crate::{hittable, onb, utils, vec3};
+
+// A probability density over directions leaving a point. Sampling a direction with generate()
+// and dividing its contribution by value() gives an unbiased estimate as long as the density is
+// nonzero wherever the integrand is. generate() maps a point u of the unit square to a
+// direction, so stratified or low-discrepancy points give well spread directions.
+pub trait Pdf {
+    fn value(&self, direction: vec3::Vec3) -> f64;
+
+    fn generate(&self, u: (f64, f64)) -> vec3::Vec3;
+}
+
+// Directions about a normal with density cos(theta) / pi, matching a Lambertian surface.
+pub struct CosinePdf {
+    uvw: onb::Onb,
+}
+
+impl CosinePdf {
+    pub fn new(w: vec3::Vec3) -> Self {
+        Self {
+            uvw: onb::Onb::new(w),
+        }
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: vec3::Vec3) -> f64 {
+        let cosine_theta = vec3::dot(vec3::unit_vector(direction), self.uvw.w());
+        (cosine_theta / utils::PI).max(0.0)
+    }
+
+    fn generate(&self, u: (f64, f64)) -> vec3::Vec3 {
+        self.uvw.transform(vec3::sample_cosine_direction(u))
+    }
+}
+
+// Directions spread evenly over the whole sphere, matching an isotropic phase function.
+pub struct SpherePdf;
+
+impl Pdf for SpherePdf {
+    fn value(&self, _direction: vec3::Vec3) -> f64 {
+        1.0 / (4.0 * utils::PI)
+    }
+
+    fn generate(&self, u: (f64, f64)) -> vec3::Vec3 {
+        vec3::sample_sphere(u)
+    }
+}
+
+// Directions from origin towards points on a set of objects, usually the scene's lights.
+pub struct HittablePdf<'a> {
+    objects: &'a dyn hittable::Hittable,
+    origin: vec3::Point3,
+}
+
+impl<'a> HittablePdf<'a> {
+    pub fn new(objects: &'a dyn hittable::Hittable, origin: vec3::Point3) -> Self {
+        Self { objects, origin }
+    }
+}
+
+impl Pdf for HittablePdf<'_> {
+    fn value(&self, direction: vec3::Vec3) -> f64 {
+        self.objects.pdf_value(self.origin, direction)
+    }
+
+    fn generate(&self, u: (f64, f64)) -> vec3::Vec3 {
+        self.objects.random(self.origin, u)
+    }
+}
+
+// An even mix of two densities. Sampling the mixture and dividing by its combined value is
+// one-sample multiple importance sampling with the balance heuristic: each strategy covers the
+// directions the other samples poorly, without counting any light twice.
+pub struct MixturePdf<'a> {
+    p: [&'a dyn Pdf; 2],
+}
+
+impl<'a> MixturePdf<'a> {
+    pub fn new(p0: &'a dyn Pdf, p1: &'a dyn Pdf) -> Self {
+        Self { p: [p0, p1] }
+    }
+}
+
+impl Pdf for MixturePdf<'_> {
+    fn value(&self, direction: vec3::Vec3) -> f64 {
+        0.5 * self.p[0].value(direction) + 0.5 * self.p[1].value(direction)
+    }
+
+    fn generate(&self, (u1, u2): (f64, f64)) -> vec3::Vec3 {
+        // The first coordinate picks a density and is stretched back over [0, 1) for it.
+        if u1 < 0.5 {
+            self.p[0].generate((2.0 * u1, u2))
+        } else {
+            self.p[1].generate((2.0 * u1 - 1.0, u2))
+        }
+    }
+}
+
+// A mix of several densities in the given proportions, such as the lobes of a layered
+// material. Like MixturePdf, sampling it and dividing by its value weighs the lobes by the
+// balance heuristic.
+pub struct WeightedPdf {
+    lobes: Vec<(f64, Box<dyn Pdf>)>,
+}
+
+impl WeightedPdf {
+    pub fn new(lobes: Vec<(f64, Box<dyn Pdf>)>) -> Self {
+        // The weights are normalised to sum to one; lobes without weight are dropped.
+        let total: f64 = lobes.iter().map(|(w, _)| w.max(0.0)).sum();
+        let lobes = lobes
+            .into_iter()
+            .filter(|(w, _)| *w > 0.0)
+            .map(|(w, p)| (w / total, p))
+            .collect();
+        Self { lobes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lobes.is_empty()
+    }
+}
+
+impl Pdf for WeightedPdf {
+    fn value(&self, direction: vec3::Vec3) -> f64 {
+        self.lobes.iter().map(|(w, p)| w * p.value(direction)).sum()
+    }
+
+    fn generate(&self, (u1, u2): (f64, f64)) -> vec3::Vec3 {
+        // As in MixturePdf, the first coordinate picks a lobe and is stretched back over [0, 1).
+        let mut start = 0.0;
+        for (i, (w, p)) in self.lobes.iter().enumerate() {
+            if u1 < start + w || i + 1 == self.lobes.len() {
+                return p.generate((((u1 - start) / w).clamp(0.0, 1.0), u2));
+            }
+            start += w;
+        }
+        vec3::Vec3::new(1.0, 0.0, 0.0)
+    }
+}
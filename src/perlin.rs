@@ -0,0 +1,96 @@
+use crate::{utils, vec3};
+
+const POINT_COUNT: usize = 256;
+
+// Ken Perlin's gradient noise: random unit vectors on a lattice, blended with a Hermite
+// cubic so the result has no visible grid artifacts.
+pub struct Perlin {
+    randvec: Vec<vec3::Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(rng: &mut utils::Rng) -> Self {
+        let randvec = (0..POINT_COUNT)
+            .map(|_| vec3::unit_vector(vec3::Vec3::new_random_bounded(rng, -1.0, 1.0)))
+            .collect();
+        Self {
+            randvec,
+            perm_x: generate_perm(rng),
+            perm_y: generate_perm(rng),
+            perm_z: generate_perm(rng),
+        }
+    }
+
+    pub fn noise(&self, p: vec3::Point3) -> f64 {
+        let u = p.x() - p.x().floor();
+        let v = p.y() - p.y().floor();
+        let w = p.z() - p.z().floor();
+
+        let i = p.x().floor() as i64;
+        let j = p.y().floor() as i64;
+        let k = p.z().floor() as i64;
+
+        let mut c = [[[vec3::Vec3::default(); 2]; 2]; 2];
+        for (di, plane) in c.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    *corner = self.randvec[self.perm_x[((i + di as i64) & 255) as usize]
+                        ^ self.perm_y[((j + dj as i64) & 255) as usize]
+                        ^ self.perm_z[((k + dk as i64) & 255) as usize]];
+                }
+            }
+        }
+
+        perlin_interp(&c, u, v, w)
+    }
+
+    pub fn turb(&self, p: vec3::Point3, depth: usize) -> f64 {
+        // Sum of noise at doubling frequencies and halving weights.
+        let mut accum = 0.0;
+        let mut temp_p = p;
+        let mut weight = 1.0;
+
+        for _ in 0..depth {
+            accum += weight * self.noise(temp_p);
+            weight *= 0.5;
+            temp_p = temp_p * 2.0;
+        }
+
+        accum.abs()
+    }
+}
+
+fn generate_perm(rng: &mut utils::Rng) -> Vec<usize> {
+    let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+    // Fisher-Yates shuffle
+    for i in (1..POINT_COUNT).rev() {
+        let target = (utils::random_double(rng) * ((i + 1) as f64)) as usize;
+        p.swap(i, target.min(i));
+    }
+    p
+}
+
+fn perlin_interp(c: &[[[vec3::Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+    let uu = u * u * (3.0 - 2.0 * u);
+    let vv = v * v * (3.0 - 2.0 * v);
+    let ww = w * w * (3.0 - 2.0 * w);
+    let mut accum = 0.0;
+
+    for (i, plane) in c.iter().enumerate() {
+        for (j, row) in plane.iter().enumerate() {
+            for (k, corner) in row.iter().enumerate() {
+                let (fi, fj, fk) = (i as f64, j as f64, k as f64);
+                let weight_v = vec3::Vec3::new(u - fi, v - fj, w - fk);
+                accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                    * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                    * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                    * vec3::dot(*corner, weight_v);
+            }
+        }
+    }
+
+    accum
+}
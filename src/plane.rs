@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use crate::{aabb, hittable, interval, material, onb, ray, vec3};
+
+// An infinite plane through point with the given normal, for floors and backdrops. Its (u, v)
+// are the hit's coordinates along two in-plane axes, wrapped to repeat every unit so image
+// textures tile.
+pub struct Plane {
+    point: vec3::Point3,
+    frame: onb::Onb, // w is the normal; u and v span the plane
+    mat: Arc<dyn material::Material>,
+    bbox: aabb::Aabb,
+}
+
+impl Plane {
+    pub fn new(point: vec3::Point3, normal: vec3::Vec3, mat: Arc<dyn material::Material>) -> Self {
+        let frame = onb::Onb::new(normal);
+        // Unbounded along every axis the plane extends in; a plane facing straight down an axis
+        // is still thin along that one.
+        let n = frame.w();
+        let extent = |axis: usize, c: f64| {
+            if 1.0 - c.abs() < 1e-12 {
+                interval::Interval::new(point.axis(axis), point.axis(axis))
+            } else {
+                interval::UNIVERSE
+            }
+        };
+        let bbox = aabb::Aabb::new(extent(0, n.x()), extent(1, n.y()), extent(2, n.z())).padded();
+        Self {
+            point,
+            frame,
+            mat,
+            bbox,
+        }
+    }
+}
+
+impl hittable::Hittable for Plane {
+    fn hit(&self, r: ray::Ray, ray_t: interval::Interval, rec: &mut hittable::HitRecord) -> bool {
+        let normal = self.frame.w();
+        let denom = vec3::dot(normal, r.direction());
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+
+        let t = vec3::dot(normal, self.point - r.origin()) / denom;
+        if !ray_t.surrounds(t) {
+            return false;
+        }
+
+        let p = r.at(t);
+        let offset = p - self.point;
+        let u = vec3::dot(offset, self.frame.transform(vec3::Vec3::new(1.0, 0.0, 0.0)));
+        let v = vec3::dot(offset, self.frame.transform(vec3::Vec3::new(0.0, 1.0, 0.0)));
+        rec.set_t(t);
+        rec.set_p(p);
+        rec.set_uv(u.rem_euclid(1.0), v.rem_euclid(1.0));
+        rec.set_mat(self.mat.clone());
+        rec.set_face_normal(r, normal);
+
+        true
+    }
+
+    fn bounding_box(&self) -> aabb::Aabb {
+        self.bbox
+    }
+}
@@ -0,0 +1,109 @@
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::{film, output};
+
+// Views of a render while it converges. The preview file is rewritten after the first pass and
+// then every few seconds or passes; the window, when built with the `window` feature, shows the
+// film after every pass.
+
+pub struct PreviewFile {
+    path: PathBuf,
+    format: output::Format,
+    interval: Duration,
+    every_passes: Option<u64>, // Write after every this many passes instead of on a timer
+    passes: u64,
+    last_write: Option<Instant>,
+}
+
+impl PreviewFile {
+    pub fn new(path: PathBuf, interval: Duration, every_passes: Option<u64>) -> io::Result<Self> {
+        let format = output::Format::from_path(&path)?;
+        Ok(Self {
+            path,
+            format,
+            interval,
+            every_passes,
+            passes: 0,
+            last_write: None,
+        })
+    }
+
+    pub fn after_pass(&mut self, film: &film::Film) -> io::Result<()> {
+        self.passes += 1;
+        let due = match (self.every_passes, self.last_write) {
+            (_, None) => true,
+            (Some(n), Some(_)) => self.passes.is_multiple_of(n),
+            (None, Some(last)) => last.elapsed() >= self.interval,
+        };
+        if due {
+            self.write(film)?;
+        }
+        Ok(())
+    }
+
+    pub fn write(&mut self, film: &film::Film) -> io::Result<()> {
+        // Written under a temporary name and renamed into place, so an image viewer watching
+        // the file never picks up a half-written image.
+        let mut tmp = self.path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        output::write_image(&film.image(), self.format, &tmp)?;
+        std::fs::rename(&tmp, &self.path)?;
+        self.last_write = Some(Instant::now());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "window")]
+pub struct PreviewWindow {
+    window: minifb::Window,
+    buffer: Vec<u32>,
+    width: usize,
+    height: usize,
+}
+
+#[cfg(feature = "window")]
+impl PreviewWindow {
+    pub fn new(width: usize, height: usize) -> Result<Self, minifb::Error> {
+        let mut window = minifb::Window::new(
+            "Ray Tracing in One Weekend",
+            width,
+            height,
+            minifb::WindowOptions {
+                resize: true,
+                ..minifb::WindowOptions::default()
+            },
+        )?;
+        window.set_target_fps(30);
+        Ok(Self {
+            window,
+            buffer: vec![0; width * height],
+            width,
+            height,
+        })
+    }
+
+    pub fn after_pass(&mut self, film: &film::Film) -> io::Result<()> {
+        // Closing the window only stops the preview; the render carries on.
+        if !self.window.is_open() {
+            return Ok(());
+        }
+        let image = film.image();
+        for (out, &c) in self.buffer.iter_mut().zip(image.pixels()) {
+            let [r, g, b] = crate::color::to_bytes(c);
+            *out = u32::from_be_bytes([0, r, g, b]);
+        }
+        self.window
+            .update_with_buffer(&self.buffer, self.width, self.height)
+            .map_err(io::Error::other)
+    }
+
+    pub fn wait_until_closed(&mut self) {
+        // Keeps showing the finished image until the window is closed or Escape is pressed.
+        while self.window.is_open() && !self.window.is_key_down(minifb::Key::Escape) {
+            self.window.update();
+        }
+    }
+}
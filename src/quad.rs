@@ -0,0 +1,137 @@
+use std::sync::Arc;
+
+use crate::{aabb, hittable, hittable_list, interval, material, ray, vec3};
+
+// A parallelogram with corner q and edges u and v. Hits get (u, v) coordinates running from 0
+// to 1 along the two edges.
+pub struct Quad {
+    q: vec3::Point3,
+    u: vec3::Vec3,
+    v: vec3::Vec3,
+    w: vec3::Vec3, // n / (n . n), for solving the planar coordinates of a hit
+    mat: Arc<dyn material::Material>,
+    bbox: aabb::Aabb,
+    normal: vec3::Vec3,
+    d: f64, // Plane constant: normal . p == d for every point p of the plane
+    area: f64,
+}
+
+impl Quad {
+    pub fn new(
+        q: vec3::Point3,
+        u: vec3::Vec3,
+        v: vec3::Vec3,
+        mat: Arc<dyn material::Material>,
+    ) -> Self {
+        let n = vec3::cross(u, v);
+        let normal = vec3::unit_vector(n);
+        // Bound both diagonals so the box covers all four corners.
+        let bbox = aabb::Aabb::surrounding(
+            aabb::Aabb::from_points(q, q + u + v),
+            aabb::Aabb::from_points(q + u, q + v),
+        )
+        .padded();
+        Self {
+            q,
+            u,
+            v,
+            w: n / vec3::dot(n, n),
+            mat,
+            bbox,
+            normal,
+            d: vec3::dot(normal, q),
+            area: n.length(),
+        }
+    }
+}
+
+impl hittable::Hittable for Quad {
+    fn hit(&self, r: ray::Ray, ray_t: interval::Interval, rec: &mut hittable::HitRecord) -> bool {
+        let denom = vec3::dot(self.normal, r.direction());
+
+        // No hit if the ray is parallel to the plane.
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+
+        // Return false if the hit point parameter t is outside the ray interval.
+        let t = (self.d - vec3::dot(self.normal, r.origin())) / denom;
+        if !ray_t.surrounds(t) {
+            return false;
+        }
+
+        // Determine if the hit point lies within the planar shape using its plane coordinates.
+        let intersection = r.at(t);
+        let planar_hitpt_vector = intersection - self.q;
+        let alpha = vec3::dot(self.w, vec3::cross(planar_hitpt_vector, self.v));
+        let beta = vec3::dot(self.w, vec3::cross(self.u, planar_hitpt_vector));
+        let unit_interval = interval::Interval::new(0.0, 1.0);
+        if !unit_interval.contains(alpha) || !unit_interval.contains(beta) {
+            return false;
+        }
+
+        rec.set_t(t);
+        rec.set_p(intersection);
+        rec.set_uv(alpha, beta);
+        rec.set_mat(self.mat.clone());
+        rec.set_face_normal(r, self.normal);
+
+        true
+    }
+
+    fn bounding_box(&self) -> aabb::Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: vec3::Point3, direction: vec3::Vec3) -> f64 {
+        // random() is uniform over the quad's area; converting that to solid angle divides by
+        // the cosine at the light and multiplies by the squared distance.
+        let mut rec = hittable::HitRecord::default();
+        let r = ray::Ray::new(origin, direction);
+        if !self.hit(r, interval::Interval::new(0.001, f64::INFINITY), &mut rec) {
+            return 0.0;
+        }
+
+        let distance_squared = rec.t() * rec.t() * direction.length_squared();
+        let cosine = (vec3::dot(direction, rec.normal()) / direction.length()).abs();
+        if cosine < 1e-8 {
+            return 0.0;
+        }
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random(&self, origin: vec3::Point3, (a, b): (f64, f64)) -> vec3::Vec3 {
+        let p = self.q + (a * self.u) + (b * self.v);
+        p - origin
+    }
+}
+
+pub fn new_box(
+    a: vec3::Point3,
+    b: vec3::Point3,
+    mat: Arc<dyn material::Material>,
+) -> hittable_list::HittableList {
+    // Returns the 3D box (six sides) that contains the two opposite vertices a & b.
+    let mut sides = hittable_list::HittableList::new();
+
+    // Construct the two opposite vertices with the minimum and maximum coordinates.
+    let min = vec3::Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+    let max = vec3::Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+
+    let dx = vec3::Vec3::new(max.x() - min.x(), 0.0, 0.0);
+    let dy = vec3::Vec3::new(0.0, max.y() - min.y(), 0.0);
+    let dz = vec3::Vec3::new(0.0, 0.0, max.z() - min.z());
+
+    let sides_desc = [
+        (vec3::Point3::new(min.x(), min.y(), max.z()), dx, dy), // front
+        (vec3::Point3::new(max.x(), min.y(), max.z()), -dz, dy), // right
+        (vec3::Point3::new(max.x(), min.y(), min.z()), -dx, dy), // back
+        (vec3::Point3::new(min.x(), min.y(), min.z()), dz, dy), // left
+        (vec3::Point3::new(min.x(), max.y(), max.z()), dx, -dz), // top
+        (vec3::Point3::new(min.x(), min.y(), min.z()), dx, dz), // bottom
+    ];
+    for (q, u, v) in sides_desc {
+        sides.add(Arc::new(Quad::new(q, u, v, mat.clone())));
+    }
+    sides
+}
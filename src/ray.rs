@@ -4,13 +4,32 @@ use crate::vec3;
 pub struct Ray {
     orig: vec3::Point3,
     direction: vec3::Vec3,
+    tm: f64,
+    wavelength: Option<f64>, // Nanometers, set only when rendering spectrally
+    medium_sample: f64,      // Uniform number media turn into this segment's free-flight distance
 }
 
 impl Ray {
     pub fn new(origin: vec3::Point3, dir: vec3::Vec3) -> Self {
+        Ray::new_with_time(origin, dir, 0.0)
+    }
+
+    pub fn new_with_time(origin: vec3::Point3, dir: vec3::Vec3, time: f64) -> Self {
         Ray {
             orig: origin,
             direction: dir,
+            tm: time,
+            wavelength: None,
+            medium_sample: 0.5,
+        }
+    }
+
+    pub fn with_line(&self, origin: vec3::Point3, dir: vec3::Vec3) -> Self {
+        // The same ray along another line, e.g. seen from an object's own space.
+        Ray {
+            orig: origin,
+            direction: dir,
+            ..*self
         }
     }
 
@@ -22,6 +41,26 @@ impl Ray {
         self.direction
     }
 
+    pub fn time(&self) -> f64 {
+        self.tm
+    }
+
+    pub fn wavelength(&self) -> Option<f64> {
+        self.wavelength
+    }
+
+    pub fn set_wavelength(&mut self, wavelength: f64) {
+        self.wavelength = Some(wavelength);
+    }
+
+    pub fn medium_sample(&self) -> f64 {
+        self.medium_sample
+    }
+
+    pub fn set_medium_sample(&mut self, u: f64) {
+        self.medium_sample = u;
+    }
+
     pub fn at(&self, t: f64) -> vec3::Point3 {
         self.orig + (self.direction * t)
     }
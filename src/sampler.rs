@@ -0,0 +1,462 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use crate::utils;
+
+// Sample points for the pixel, lens, time and scattering dimensions of each camera path. A
+// sampler is told which pixel sample it is drawing for, then hands out consecutive dimensions:
+// the pixel offset, the lens position, the shutter time and one 2D point per bounce. Every value
+// depends only on the pixel, the sample index, the dimension and the seed, so renders stay
+// reproducible no matter how rows are spread over threads.
+pub trait Sampler {
+    fn start_pixel_sample(&mut self, x: i64, y: i64, index: i64);
+
+    fn get_1d(&mut self) -> f64;
+
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplerKind {
+    #[default]
+    Independent, // Uniform random numbers
+    Stratified, // One jittered sample per stratum, strata shuffled per dimension
+    Halton,     // Halton sequence, randomly shifted per pixel
+    Sobol,      // Owen-scrambled Sobol points, padded pairwise across dimensions
+    BlueNoise,  // Rank-1 lattice offset per pixel by a blue-noise mask
+}
+
+impl SamplerKind {
+    pub fn build(self, samples_per_pixel: i64, seed: u64) -> Box<dyn Sampler> {
+        let state = PixelSample {
+            seed,
+            ..PixelSample::default()
+        };
+        match self {
+            SamplerKind::Independent => Box::new(IndependentSampler {
+                state,
+                rng: utils::Rng::new(seed),
+            }),
+            SamplerKind::Stratified => Box::new(StratifiedSampler {
+                state,
+                samples_per_pixel: samples_per_pixel.max(1) as u64,
+            }),
+            SamplerKind::Halton => Box::new(HaltonSampler { state }),
+            SamplerKind::Sobol => Box::new(SobolSampler { state }),
+            SamplerKind::BlueNoise => Box::new(BlueNoiseSampler { state }),
+        }
+    }
+}
+
+impl FromStr for SamplerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('-', "_").as_str() {
+            "independent" => Ok(SamplerKind::Independent),
+            "stratified" => Ok(SamplerKind::Stratified),
+            "halton" => Ok(SamplerKind::Halton),
+            "sobol" => Ok(SamplerKind::Sobol),
+            "blue_noise" => Ok(SamplerKind::BlueNoise),
+            _ => Err(format!(
+                "unknown sampler `{}`, expected one of independent, stratified, halton, sobol, \
+                 blue_noise",
+                s
+            )),
+        }
+    }
+}
+
+// Which pixel sample is being drawn and how many dimensions it has used so far.
+#[derive(Default)]
+struct PixelSample {
+    seed: u64,
+    x: i64,
+    y: i64,
+    index: u64,
+    dimension: u64,
+}
+
+impl PixelSample {
+    fn start(&mut self, x: i64, y: i64, index: i64) {
+        self.x = x;
+        self.y = y;
+        self.index = index as u64;
+        self.dimension = 0;
+    }
+
+    fn next_dimensions(&mut self, count: u64) -> u64 {
+        let dimension = self.dimension;
+        self.dimension += count;
+        dimension
+    }
+
+    // A hash of the pixel, the dimension and the seed, for per-pixel randomization.
+    fn pixel_hash(&self, dimension: u64) -> u64 {
+        utils::hash(&[self.seed, self.x as u64, self.y as u64, dimension])
+    }
+}
+
+struct IndependentSampler {
+    state: PixelSample,
+    rng: utils::Rng,
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, x: i64, y: i64, index: i64) {
+        self.state.start(x, y, index);
+        let stream = self.state.pixel_hash(index as u64);
+        self.rng = utils::Rng::for_stream(self.state.seed, stream);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        utils::random_double(&mut self.rng)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (
+            utils::random_double(&mut self.rng),
+            utils::random_double(&mut self.rng),
+        )
+    }
+}
+
+struct StratifiedSampler {
+    state: PixelSample,
+    samples_per_pixel: u64,
+}
+
+impl StratifiedSampler {
+    // The stratum this sample falls in out of count, shuffled differently for every pixel and
+    // dimension so that dimensions don't line up with each other, plus random bits for the
+    // jitter inside it.
+    fn stratum(&mut self, count: u64) -> (u64, u64) {
+        let dimension = self.state.next_dimensions(1);
+        let hash = self.state.pixel_hash(dimension);
+        let index = self.state.index % count;
+        let stratum = permutation_element(index as u32, count as u32, hash as u32) as u64;
+        (stratum, utils::hash(&[hash, index]))
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, x: i64, y: i64, index: i64) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let (stratum, jitter) = self.stratum(self.samples_per_pixel);
+        (stratum as f64 + hash_to_unit(jitter)) / self.samples_per_pixel as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        // A grid of at least samples_per_pixel cells, as close to square as possible.
+        let nx = (self.samples_per_pixel as f64).sqrt().ceil() as u64;
+        let ny = self.samples_per_pixel.div_ceil(nx);
+        let (stratum, jitter) = self.stratum(nx * ny);
+        (
+            ((stratum % nx) as f64 + hash_to_unit(jitter)) / nx as f64,
+            ((stratum / nx) as f64 + hash_to_unit(jitter >> 32)) / ny as f64,
+        )
+    }
+}
+
+const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+struct HaltonSampler {
+    state: PixelSample,
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, x: i64, y: i64, index: i64) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.state.next_dimensions(1);
+        let hash = self.state.pixel_hash(dimension);
+        match PRIMES.get(dimension as usize) {
+            // Every pixel walks the same sequence, rotated by its own offset per dimension.
+            Some(&base) => (radical_inverse(base, self.state.index) + hash_to_unit(hash)).fract(),
+            // Past the last prime the sequence's quality drops, so fall back to hashing.
+            None => hash_to_unit(utils::hash(&[hash, self.state.index])),
+        }
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+struct SobolSampler {
+    state: PixelSample,
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, x: i64, y: i64, index: i64) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.get_2d().0
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        // Each pair of dimensions uses the first two Sobol dimensions, which form a (0, 2)
+        // sequence, with its own shuffle of the sample order and Owen scrambling of the points
+        // (Burley, "Practical Hash-based Owen Scrambling", 2020).
+        let dimension = self.state.next_dimensions(2);
+        let hash = self.state.pixel_hash(dimension);
+        let index = nested_uniform_scramble(self.state.index as u32, hash as u32);
+        let x = nested_uniform_scramble(sobol_dimension0(index), (hash >> 32) as u32);
+        let y = nested_uniform_scramble(sobol_dimension1(index), utils::hash(&[hash]) as u32);
+        (u32_to_unit(x), u32_to_unit(y))
+    }
+}
+
+struct BlueNoiseSampler {
+    state: PixelSample,
+}
+
+impl BlueNoiseSampler {
+    // The mask value at this pixel, with the mask shifted by a different amount for every
+    // dimension so dimensions don't share a pattern.
+    fn mask_offset(&self, dimension: u64) -> f64 {
+        let hash = utils::hash(&[self.state.seed, dimension]);
+        let size = BLUE_NOISE_SIZE as i64;
+        let x = (self.state.x + (hash % BLUE_NOISE_SIZE as u64) as i64).rem_euclid(size);
+        let y = (self.state.y + ((hash >> 32) % BLUE_NOISE_SIZE as u64) as i64).rem_euclid(size);
+        blue_noise_mask()[(y * size + x) as usize]
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn start_pixel_sample(&mut self, x: i64, y: i64, index: i64) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        // Golden ratio sequence, offset per pixel by the mask so that neighbouring pixels get
+        // different points and their errors spread as blue noise rather than clumping.
+        const ALPHA: f64 = 0.618_033_988_749_894_9;
+        let dimension = self.state.next_dimensions(1);
+        (0.5 + ALPHA * self.state.index as f64 + self.mask_offset(dimension)).fract()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        // Roberts' R2 sequence, built on the plastic number, offset the same way.
+        const G: f64 = 1.324_717_957_244_746;
+        let dimension = self.state.next_dimensions(2);
+        let n = self.state.index as f64;
+        (
+            (0.5 + n / G + self.mask_offset(dimension)).fract(),
+            (0.5 + n / (G * G) + self.mask_offset(dimension + 1)).fract(),
+        )
+    }
+}
+
+fn hash_to_unit(hash: u64) -> f64 {
+    u32_to_unit(hash as u32)
+}
+
+fn u32_to_unit(x: u32) -> f64 {
+    x as f64 / 4_294_967_296.0
+}
+
+fn radical_inverse(base: u64, mut index: u64) -> f64 {
+    // Mirrors the digits of index in the given base around the radix point.
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_n = 1.0;
+    let mut reversed = 0;
+    while index > 0 {
+        let next = index / base;
+        reversed = reversed * base + (index - next * base);
+        inv_base_n *= inv_base;
+        index = next;
+    }
+    (reversed as f64 * inv_base_n).min(1.0 - f64::EPSILON)
+}
+
+fn permutation_element(mut i: u32, len: u32, p: u32) -> u32 {
+    // Element i of a pseudo-random permutation of 0..len chosen by p, without building the
+    // permutation (Kensler, "Correlated Multi-Jittered Sampling", 2013).
+    let mut w = len - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < len {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % len
+}
+
+fn sobol_dimension0(index: u32) -> u32 {
+    // The van der Corput sequence.
+    index.reverse_bits()
+}
+
+fn sobol_dimension1(mut index: u32) -> u32 {
+    let mut v: u32 = 1 << 31;
+    let mut x = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            x ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    x
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    // Owen scrambling: a random permutation of each binary digit that depends on all the
+    // higher digits, done as a hash on the bit-reversed value.
+    let mut x = x.reverse_bits();
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x.reverse_bits()
+}
+
+const BLUE_NOISE_SIZE: usize = 64;
+
+fn blue_noise_mask() -> &'static [f64] {
+    static MASK: OnceLock<Vec<f64>> = OnceLock::new();
+    MASK.get_or_init(|| void_and_cluster(BLUE_NOISE_SIZE))
+}
+
+fn void_and_cluster(size: usize) -> Vec<f64> {
+    // Ulichney's void-and-cluster method: rank every cell of a tileable size x size mask so
+    // that the first n cells of any prefix are as evenly spread as possible. Returns each cell's
+    // rank scaled into [0, 1).
+    let n = size * size;
+    let sigma = 1.5;
+    let mut kernel = vec![0.0; n];
+    for dy in 0..size {
+        for dx in 0..size {
+            // Toroidal distance, so the mask tiles seamlessly.
+            let wx = dx.min(size - dx) as f64;
+            let wy = dy.min(size - dy) as f64;
+            kernel[dy * size + dx] = (-(wx * wx + wy * wy) / (2.0 * sigma * sigma)).exp();
+        }
+    }
+    let splat = |energy: &mut [f64], cell: usize, sign: f64| {
+        let (cx, cy) = (cell % size, cell / size);
+        for y in 0..size {
+            for x in 0..size {
+                let k = kernel[((y + size - cy) % size) * size + (x + size - cx) % size];
+                energy[y * size + x] += sign * k;
+            }
+        }
+    };
+    // The cell of the given state with the highest (tightest cluster) or lowest (largest void)
+    // energy.
+    let extreme = |pattern: &[bool], energy: &[f64], state: bool, highest: bool| {
+        (0..n)
+            .filter(|&i| pattern[i] == state)
+            .max_by(|&a, &b| {
+                let order = energy[a].total_cmp(&energy[b]);
+                if highest {
+                    order
+                } else {
+                    order.reverse()
+                }
+            })
+            .unwrap()
+    };
+
+    // Start from a sparse random pattern and move points from clusters into voids until it
+    // settles.
+    let mut rng = utils::Rng::new(0);
+    let mut pattern = vec![false; n];
+    let initial = n / 10;
+    let mut placed = 0;
+    while placed < initial {
+        let cell = ((utils::random_double(&mut rng) * n as f64) as usize).min(n - 1);
+        if !pattern[cell] {
+            pattern[cell] = true;
+            placed += 1;
+        }
+    }
+    let mut energy = vec![0.0; n];
+    for cell in (0..n).filter(|&i| pattern[i]) {
+        splat(&mut energy, cell, 1.0);
+    }
+    // Ties in the energy can make the swaps cycle instead, so the loop also stops when a swap
+    // repeats and after at most one swap per cell.
+    let mut swaps = HashSet::new();
+    for _ in 0..n {
+        let cluster = extreme(&pattern, &energy, true, true);
+        pattern[cluster] = false;
+        splat(&mut energy, cluster, -1.0);
+        let void = extreme(&pattern, &energy, false, false);
+        pattern[void] = true;
+        splat(&mut energy, void, 1.0);
+        if void == cluster || !swaps.insert((cluster, void)) {
+            break;
+        }
+    }
+
+    let mut rank = vec![0; n];
+    // Rank the initial points by removing the tightest cluster first.
+    let (mut ones, mut ones_energy) = (pattern.clone(), energy.clone());
+    for r in (0..initial).rev() {
+        let cluster = extreme(&ones, &ones_energy, true, true);
+        ones[cluster] = false;
+        splat(&mut ones_energy, cluster, -1.0);
+        rank[cluster] = r;
+    }
+    // Fill the largest voids up to half the cells.
+    for r in initial..n / 2 {
+        let void = extreme(&pattern, &energy, false, false);
+        pattern[void] = true;
+        splat(&mut energy, void, 1.0);
+        rank[void] = r;
+    }
+    // Past half, the empty cells are the minority, so fill the tightest cluster of them.
+    let mut empty_energy = vec![0.0; n];
+    for cell in (0..n).filter(|&i| !pattern[i]) {
+        splat(&mut empty_energy, cell, 1.0);
+    }
+    for r in n / 2..n {
+        let cluster = extreme(&pattern, &empty_energy, false, true);
+        pattern[cluster] = true;
+        splat(&mut empty_energy, cluster, -1.0);
+        rank[cluster] = r;
+    }
+
+    rank.into_iter()
+        .map(|r| (r as f64 + 0.5) / n as f64)
+        .collect()
+}
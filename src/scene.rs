@@ -0,0 +1,1412 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::{error, fmt, fs, io};
+
+use serde::{de, Deserialize, Deserializer};
+
+use crate::{
+    bvh, camera, color, constant_medium, disk, environment, hittable, hittable_list, input,
+    instance, mat4, material, mesh, obj, plane, quad, sampler, sphere, texture, triangle, utils,
+    vec3,
+};
+
+// A scene file is TOML with a `[camera]` table, a `[materials.<name>]` table per material and an
+// `[[objects]]` entry per object. Every camera field is optional and falls back to the defaults
+// of the built-in final scene; `background` is "sky" or an RGB color such as black for scenes
+// lit only by `diffuse_light` materials. `spectral = true` traces paths at sampled wavelengths.
+//
+//     [camera]
+//     lookfrom = [13.0, 2.0, 3.0]
+//
+//     [materials.ground]
+//     type = "lambertian"
+//     albedo = [0.5, 0.5, 0.5]
+//
+//     [[objects]]
+//     type = "sphere"
+//     center = [0.0, -1000.0, 0.0]
+//     radius = 1000.0
+//     material = "ground"
+//
+// Objects are spheres (`center`, `radius`), triangles (`vertices` and optional per-vertex
+// `normals`), meshes (`positions`, optional `normals` and `indices` into them) or Wavefront OBJ
+// models (`path`, with `material` as the fallback for faces without an MTL material).
+//
+// Flat shapes are quads (a corner `q` and edge vectors `u` and `v`), axis-aligned boxes (two
+// opposite corners `a` and `b`), disks (`center`, `normal`, `radius`) and infinite planes
+// (`point`, `normal`). Quads map textures across their edges, disks around their center and
+// planes repeat them every unit.
+//
+// A sphere with `center2` moves linearly from `center` at time 0 to `center2` at time 1, and
+// is motion blurred over the camera's `shutter_open`..`shutter_close` interval.
+//
+// A `[shapes.<name>]` table describes any of these objects once without placing it; `instance`
+// objects then place a `shape` with an optional `scale` (a number or one per axis), `rotate`
+// (degrees about x, y and z, applied in that order), `translate` and an affine 4x4 `matrix`
+// given as rows, applied in that order.
+//
+// A `medium` fills a shape, placed the same way, with smoke or fog of a uniform `density` made of
+// an `isotropic` material (`albedo`), which scatters light evenly in every direction.
+//
+// Besides the book's materials there are GGX microfacet ones: `conductor` (a `metal` preset of
+// "gold", "copper" or "aluminium", or RGB `eta` and `k`) and `rough_dielectric`
+// (`refraction_index`), both with `roughness` and `anisotropy` between 0 and 1. A `dielectric`
+// disperses light in spectral renders when it gives `cauchy = [a, b]` or
+// `sellmeier = { b = [...], c = [...] }` coefficients instead of a `refraction_index`.
+//
+// A `principled` material blends diffuse, sheen, specular, clearcoat and glass lobes under a
+// `base_color` with `metallic`, `roughness`, `specular`, `clearcoat`, `clearcoat_roughness`,
+// `sheen`, `sheen_tint` and `transmission` between 0 and 1, plus `refraction_index` and
+// `emission`. Every parameter is a constant or the name of a texture; scalar parameters read the
+// average of the texture's channels.
+//
+// Material colors (`albedo`, `emit`) are an RGB array or the name of a `[textures.<name>]`
+// table: `solid` (`color`), `checker` (`scale`, `even`, `odd`), `image` (`path` to a PNG, JPEG,
+// Radiance HDR or PFM image) or `noise` (`scale`, `style` of "smooth", "turbulence" or "marble").
+//
+// An `[environment]` table surrounds the scene with an equirectangular HDR image (`path`), turned
+// `rotation` degrees about the y axis and scaled by `intensity`. It replaces the background and
+// is sampled as a light.
+
+pub struct Scene {
+    pub camera: CameraSettings,
+    pub world: hittable_list::HittableList,
+    pub lights: hittable_list::HittableList, // Emitters the renderer samples directly
+    pub source: u64, // Hash of the description and the asset files the scene was built from
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraSettings {
+    pub aspect_ratio: f64,
+    pub image_width: i64,
+    pub samples_per_pixel: i64,
+    pub pass_samples: i64,
+    pub noise_threshold: f64,
+    pub min_samples: i64,
+    pub sampler: sampler::SamplerKind,
+    pub max_depth: i64,
+    pub min_depth: i64,
+    pub vfov: f64,
+    pub lookfrom: vec3::Point3,
+    pub lookat: vec3::Point3,
+    pub vup: vec3::Vec3,
+    pub defocus_angle: f64,
+    pub focus_dist: f64,
+    pub background: camera::Background,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+    pub spectral: bool,
+}
+
+impl CameraSettings {
+    pub fn build(&self) -> camera::Camera {
+        let mut cam = camera::Camera::new(
+            self.aspect_ratio,
+            self.image_width,
+            self.samples_per_pixel,
+            self.max_depth,
+            self.vfov,
+            self.lookfrom,
+            self.lookat,
+            self.vup,
+            self.defocus_angle,
+            self.focus_dist,
+        );
+        cam.set_pass_samples(self.pass_samples);
+        cam.set_adaptive(self.noise_threshold, self.min_samples);
+        cam.set_sampler(self.sampler);
+        cam.set_min_depth(self.min_depth);
+        cam.set_background(self.background.clone());
+        cam.set_shutter(self.shutter_open, self.shutter_close);
+        cam.set_spectral(self.spectral);
+        cam
+    }
+
+    pub fn fingerprint(&self) -> u64 {
+        // A hash of the settings that change what each sample computes, so a checkpoint is only
+        // resumed with settings that keep adding to the same image. Sample counts and adaptive
+        // sampling only decide how many samples pixels take, so those may change between runs.
+        let point = |p: vec3::Vec3| [p.x().to_bits(), p.y().to_bits(), p.z().to_bits()];
+        let background = match &self.background {
+            camera::Background::Sky => [0; 4],
+            camera::Background::Solid(c) => [1, c.x().to_bits(), c.y().to_bits(), c.z().to_bits()],
+            // The map itself comes from the scene description.
+            camera::Background::Environment(_) => [2, 0, 0, 0],
+        };
+        let mut values = vec![
+            self.aspect_ratio.to_bits(),
+            self.image_width as u64,
+            self.sampler as u64,
+            self.max_depth as u64,
+            self.min_depth as u64,
+            self.vfov.to_bits(),
+            self.defocus_angle.to_bits(),
+            self.focus_dist.to_bits(),
+            self.shutter_open.to_bits(),
+            self.shutter_close.to_bits(),
+            self.spectral as u64,
+        ];
+        values.extend(point(self.lookfrom));
+        values.extend(point(self.lookat));
+        values.extend(point(self.vup));
+        values.extend(background);
+        utils::hash(&values)
+    }
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            aspect_ratio: 16.0 / 9.0,
+            image_width: 1200,
+            samples_per_pixel: 500,
+            pass_samples: 16,
+            noise_threshold: 0.0,
+            min_samples: 16,
+            sampler: sampler::SamplerKind::Independent,
+            max_depth: 50,
+            min_depth: 5,
+            vfov: 20.0,
+            lookfrom: vec3::Point3::new(13.0, 2.0, 3.0), // Point camera is looking from
+            lookat: vec3::Point3::new(0.0, 0.0, 0.0),    // Point camera is looking at
+            vup: vec3::Vec3::new(0.0, 1.0, 0.0),         // Camera-relative up direction
+            defocus_angle: 0.6, // Variation angle of rays through each pixel
+            focus_dist: 10.0,   // Distance from camera lookfrom point to plane of perfect focus
+            background: camera::Background::Sky,
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            spectral: false,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for camera::Background {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Either the name "sky" or an RGB color, e.g. `background = [0.0, 0.0, 0.0]`.
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Desc {
+            Named(String),
+            Color(vec3::Vec3),
+        }
+        match Desc::deserialize(deserializer)? {
+            Desc::Named(name) if name == "sky" => Ok(camera::Background::Sky),
+            Desc::Named(name) => Err(de::Error::custom(format!(
+                "unknown background `{}`, expected \"sky\" or a color",
+                name
+            ))),
+            Desc::Color(c) => Ok(camera::Background::Solid(c)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Invalid {
+        line: Option<usize>,
+        field: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "could not read scene file: {}", err),
+            Error::Parse(err) => write!(f, "invalid scene file: {}", err),
+            Error::Invalid {
+                line: Some(line),
+                field,
+                message,
+            } => write!(
+                f,
+                "invalid scene file at line {}: {}: {}",
+                line, field, message
+            ),
+            Error::Invalid {
+                line: None,
+                field,
+                message,
+            } => write!(f, "invalid scene file: {}: {}", field, message),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Self {
+        Error::Parse(err)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    #[serde(default)]
+    camera: CameraSettings,
+    #[serde(default)]
+    textures: BTreeMap<String, toml::Spanned<TextureDesc>>,
+    #[serde(default)]
+    materials: BTreeMap<String, toml::Spanned<MaterialDesc>>,
+    #[serde(default)]
+    shapes: BTreeMap<String, toml::Spanned<ObjectDesc>>,
+    #[serde(default)]
+    objects: Vec<toml::Spanned<ObjectDesc>>,
+    environment: Option<toml::Spanned<EnvironmentDesc>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentDesc {
+    path: String,
+    #[serde(default)]
+    rotation: f64,
+    #[serde(default = "default_intensity")]
+    intensity: f64,
+}
+
+fn default_intensity() -> f64 {
+    1.0
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDesc {
+    Solid {
+        color: color::Color,
+    },
+    Checker {
+        scale: f64,
+        even: ColorOrTexture,
+        odd: ColorOrTexture,
+    },
+    Image {
+        path: String,
+    },
+    Noise {
+        #[serde(default = "default_noise_scale")]
+        scale: f64,
+        #[serde(default = "default_noise_style")]
+        style: texture::NoiseStyle,
+    },
+}
+
+fn default_noise_scale() -> f64 {
+    1.0
+}
+
+fn default_noise_style() -> texture::NoiseStyle {
+    texture::NoiseStyle::Marble
+}
+
+// Material colors are either an inline RGB value or the name of a texture.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorOrTexture {
+    Color(color::Color),
+    Texture(String),
+}
+
+// Like ColorOrTexture for single numbers; a texture's channels are averaged.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScalarOrTexture {
+    Value(f64),
+    Texture(String),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDesc {
+    Lambertian {
+        albedo: ColorOrTexture,
+    },
+    Metal {
+        albedo: ColorOrTexture,
+        #[serde(default)]
+        fuzz: f64,
+    },
+    Dielectric {
+        refraction_index: Option<f64>,
+        cauchy: Option<[f64; 2]>,
+        sellmeier: Option<SellmeierDesc>,
+    },
+    DiffuseLight {
+        emit: ColorOrTexture,
+    },
+    Isotropic {
+        albedo: ColorOrTexture,
+    },
+    Conductor {
+        metal: Option<material::ConductorPreset>,
+        eta: Option<color::Color>,
+        k: Option<color::Color>,
+        #[serde(default)]
+        roughness: f64,
+        #[serde(default)]
+        anisotropy: f64,
+    },
+    RoughDielectric {
+        refraction_index: f64,
+        #[serde(default)]
+        roughness: f64,
+        #[serde(default)]
+        anisotropy: f64,
+    },
+    Principled {
+        base_color: Option<ColorOrTexture>,
+        metallic: Option<ScalarOrTexture>,
+        roughness: Option<ScalarOrTexture>,
+        specular: Option<ScalarOrTexture>,
+        clearcoat: Option<ScalarOrTexture>,
+        clearcoat_roughness: Option<ScalarOrTexture>,
+        sheen: Option<ScalarOrTexture>,
+        sheen_tint: Option<ScalarOrTexture>,
+        transmission: Option<ScalarOrTexture>,
+        refraction_index: Option<ScalarOrTexture>,
+        emission: Option<ColorOrTexture>,
+    },
+}
+
+// Sellmeier coefficients of a glass, e.g. `sellmeier = { b = [...], c = [...] }`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SellmeierDesc {
+    b: [f64; 3],
+    c: [f64; 3],
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum ObjectDesc {
+    Sphere {
+        center: vec3::Point3,
+        center2: Option<vec3::Point3>,
+        radius: f64,
+        material: String,
+    },
+    Triangle {
+        vertices: [vec3::Point3; 3],
+        normals: Option<[vec3::Vec3; 3]>,
+        material: String,
+    },
+    Mesh {
+        positions: Vec<vec3::Point3>,
+        #[serde(default)]
+        normals: Vec<vec3::Vec3>,
+        indices: Vec<[usize; 3]>,
+        material: String,
+    },
+    Obj {
+        path: String,
+        material: Option<String>,
+    },
+    Quad {
+        q: vec3::Point3,
+        u: vec3::Vec3,
+        v: vec3::Vec3,
+        material: String,
+    },
+    Box {
+        a: vec3::Point3,
+        b: vec3::Point3,
+        material: String,
+    },
+    Disk {
+        center: vec3::Point3,
+        normal: vec3::Vec3,
+        radius: f64,
+        material: String,
+    },
+    Plane {
+        point: vec3::Point3,
+        normal: vec3::Vec3,
+        material: String,
+    },
+    Instance {
+        shape: String,
+        scale: Option<Scale>,
+        rotate: Option<vec3::Vec3>,
+        translate: Option<vec3::Vec3>,
+        matrix: Option<[[f64; 4]; 4]>,
+    },
+    Medium {
+        shape: String,
+        density: f64,
+        material: String,
+        scale: Option<Scale>,
+        rotate: Option<vec3::Vec3>,
+        translate: Option<vec3::Vec3>,
+        matrix: Option<[[f64; 4]; 4]>,
+    },
+}
+
+// An instance scale is one factor for every axis or a factor per axis.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(untagged)]
+enum Scale {
+    Uniform(f64),
+    Axes(vec3::Vec3),
+}
+
+impl ObjectDesc {
+    fn material(&self) -> Option<&str> {
+        match self {
+            ObjectDesc::Sphere { material, .. }
+            | ObjectDesc::Triangle { material, .. }
+            | ObjectDesc::Mesh { material, .. }
+            | ObjectDesc::Quad { material, .. }
+            | ObjectDesc::Box { material, .. }
+            | ObjectDesc::Disk { material, .. }
+            | ObjectDesc::Plane { material, .. }
+            | ObjectDesc::Medium { material, .. } => Some(material),
+            ObjectDesc::Obj { material, .. } => material.as_deref(),
+            ObjectDesc::Instance { .. } => None,
+        }
+    }
+}
+
+pub const BUILTIN_SCENES: &[&str] = &["final"];
+
+pub fn builtin(name: &str, rng: &mut utils::Rng) -> Option<Scene> {
+    match name {
+        "final" => Some(final_scene(rng)),
+        _ => None,
+    }
+}
+
+pub fn load(path: &Path, rng: &mut utils::Rng) -> Result<Scene, Error> {
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    parse(&fs::read_to_string(path)?, base_dir, rng)
+}
+
+pub fn parse(src: &str, base_dir: &Path, rng: &mut utils::Rng) -> Result<Scene, Error> {
+    // Relative paths to models, images and other assets are resolved against base_dir.
+    let file: SceneFile = toml::from_str(src)?;
+
+    check_camera(&file.camera, src)?;
+
+    // Textures are built in name order so noise textures draw the same random numbers on
+    // every load with the same seed.
+    let mut textures = TextureSet {
+        src,
+        base_dir,
+        descs: &file.textures,
+        built: HashMap::new(),
+        files: Vec::new(),
+    };
+    for name in file.textures.keys() {
+        textures.get(name, rng, &mut Vec::new())?;
+    }
+
+    let mut materials: HashMap<&str, Arc<dyn material::Material>> = HashMap::new();
+    for (name, desc) in &file.materials {
+        let line = line_of(src, desc.span().start);
+        let field = |f: &str| format!("materials.{}.{}", name, f);
+        let mat = build_material(desc.get_ref(), &mut textures, line, field)?;
+        materials.insert(name.as_str(), mat);
+    }
+
+    // Every model and image the scene reads, so that editing one counts as a different scene.
+    let mut files = std::mem::take(&mut textures.files);
+
+    // Shapes are built once and can then be placed any number of times by instances.
+    let mut shapes: HashMap<&str, Shape> = HashMap::new();
+    for (name, desc) in &file.shapes {
+        let line = line_of(src, desc.span().start);
+        let field = |f: &str| format!("shapes.{}.{}", name, f);
+        let mat = object_material(desc.get_ref(), &materials, line, &field("material"))?;
+        let parts = build_object(desc.get_ref(), mat, base_dir, &mut files, line, field)?;
+        let mut list = hittable_list::HittableList::new();
+        for part in &parts {
+            list.add(part.clone());
+        }
+        let light: Option<Arc<dyn hittable::Hittable>> = if emits(desc.get_ref(), &file.materials) {
+            Some(Arc::new(list.clone()))
+        } else {
+            None
+        };
+        let object: Arc<dyn hittable::Hittable> = match parts.len() {
+            0 => Arc::new(list),
+            1 => parts[0].clone(),
+            _ => Arc::new(bvh::BvhNode::new(&list)),
+        };
+        shapes.insert(name.as_str(), Shape { object, light });
+    }
+
+    let mut world = hittable_list::HittableList::new();
+    let mut lights = hittable_list::HittableList::new();
+    for (i, object) in file.objects.iter().enumerate() {
+        let line = line_of(src, object.span().start);
+        let field = |name: &str| format!("objects[{}].{}", i, name);
+        match object.get_ref() {
+            ObjectDesc::Instance {
+                shape,
+                scale,
+                rotate,
+                translate,
+                matrix,
+            } => {
+                let placed = find_shape(&shapes, shape, line, &field("shape"))?;
+                let to_world = instance_transform(*scale, *rotate, *translate, *matrix)
+                    .map_err(|message| invalid(Some(line), &field("matrix"), message))?;
+                world.add(instance_of(placed.object.clone(), to_world, line, i)?);
+                if let Some(light) = &placed.light {
+                    lights.add(instance_of(light.clone(), to_world, line, i)?);
+                }
+            }
+            ObjectDesc::Medium {
+                shape,
+                density,
+                scale,
+                rotate,
+                translate,
+                matrix,
+                ..
+            } => {
+                if *density <= 0.0 {
+                    return Err(invalid(Some(line), &field("density"), "must be positive"));
+                }
+                let placed = find_shape(&shapes, shape, line, &field("shape"))?;
+                let to_world = instance_transform(*scale, *rotate, *translate, *matrix)
+                    .map_err(|message| invalid(Some(line), &field("matrix"), message))?;
+                let boundary = instance_of(placed.object.clone(), to_world, line, i)?;
+                let mat = object_material(object.get_ref(), &materials, line, &field("material"))?;
+                world.add(Arc::new(constant_medium::ConstantMedium::new(
+                    boundary, *density, mat,
+                )));
+            }
+            desc => {
+                let mat = object_material(desc, &materials, line, &field("material"))?;
+                let is_light = emits(desc, &file.materials);
+                for part in build_object(desc, mat, base_dir, &mut files, line, field)? {
+                    if is_light {
+                        lights.add(part.clone());
+                    }
+                    world.add(part);
+                }
+            }
+        }
+    }
+
+    // The renderer builds a BVH over the world, which needs at least one object; OBJ models
+    // without faces add none.
+    if world.objects().is_empty() {
+        return Err(invalid(None, "objects", "the scene has nothing to render"));
+    }
+
+    // An environment map replaces the background and is sampled like any other light.
+    let mut camera = file.camera;
+    if let Some(desc) = &file.environment {
+        let line = line_of(src, desc.span().start);
+        let env = desc.get_ref();
+        if env.intensity < 0.0 {
+            return Err(invalid(
+                Some(line),
+                "environment.intensity",
+                "must not be negative",
+            ));
+        }
+        files.push(base_dir.join(&env.path));
+        let image = input::read_image(&base_dir.join(&env.path))
+            .map_err(|err| invalid(Some(line), "environment.path", &err.to_string()))?;
+        if image.width() == 0 || image.height() == 0 {
+            return Err(invalid(Some(line), "environment.path", "image is empty"));
+        }
+        let map = Arc::new(environment::EnvironmentMap::new(
+            image,
+            env.rotation,
+            env.intensity,
+        ));
+        lights.add(map.clone());
+        camera.background = camera::Background::Environment(map);
+    }
+
+    Ok(Scene {
+        camera,
+        world,
+        lights,
+        source: utils::hash(&[hash_text(src), hash_files(&files)]),
+    })
+}
+
+fn hash_text(text: &str) -> u64 {
+    utils::hash(&text.bytes().map(u64::from).collect::<Vec<_>>())
+}
+
+fn hash_files(files: &[PathBuf]) -> u64 {
+    // Each file's path, size and modification time, which change whenever the file is edited,
+    // without reading large models and images a second time.
+    let stamps: Vec<u64> = files
+        .iter()
+        .map(|path| {
+            let metadata = fs::metadata(path).ok();
+            let modified = metadata
+                .as_ref()
+                .and_then(|m| m.modified().ok())
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_nanos() as u64);
+            utils::hash(&[
+                hash_text(&path.to_string_lossy()),
+                metadata.map_or(0, |m| m.len()),
+                modified,
+            ])
+        })
+        .collect();
+    utils::hash(&stamps)
+}
+
+// A shape ready to be instanced: the object to intersect, and for an emissive shape a list of
+// its parts that can be sampled as a light.
+struct Shape {
+    object: Arc<dyn hittable::Hittable>,
+    light: Option<Arc<dyn hittable::Hittable>>,
+}
+
+fn object_material(
+    desc: &ObjectDesc,
+    materials: &HashMap<&str, Arc<dyn material::Material>>,
+    line: usize,
+    field: &str,
+) -> Result<Arc<dyn material::Material>, Error> {
+    match desc.material() {
+        Some(name) => lookup(materials, name, line, field),
+        // Models fall back to a neutral grey for faces their own materials don't cover.
+        None => Ok(Arc::new(material::Lambertian::new(color::Color::new(
+            0.8, 0.8, 0.8,
+        )))),
+    }
+}
+
+fn emits(desc: &ObjectDesc, materials: &BTreeMap<String, toml::Spanned<MaterialDesc>>) -> bool {
+    // Objects made of a diffuse_light, or a principled material with emission, are also sampled
+    // directly as lights. An infinite plane has no area to pick points on, so an emissive one is
+    // only found by scattered rays.
+    !matches!(desc, ObjectDesc::Plane { .. })
+        && desc
+            .material()
+            .and_then(|name| materials.get(name))
+            .is_some_and(|desc| {
+                matches!(
+                    desc.get_ref(),
+                    MaterialDesc::DiffuseLight { .. }
+                        | MaterialDesc::Principled {
+                            emission: Some(_),
+                            ..
+                        }
+                )
+            })
+}
+
+fn build_object(
+    desc: &ObjectDesc,
+    mat: Arc<dyn material::Material>,
+    base_dir: &Path,
+    files: &mut Vec<PathBuf>,
+    line: usize,
+    field: impl Fn(&str) -> String,
+) -> Result<Vec<Arc<dyn hittable::Hittable>>, Error> {
+    let mut parts: Vec<Arc<dyn hittable::Hittable>> = Vec::new();
+    match desc {
+        ObjectDesc::Sphere {
+            center,
+            center2,
+            radius,
+            ..
+        } => {
+            let center2 = center2.unwrap_or(*center);
+            parts.push(Arc::new(sphere::Sphere::new_moving(
+                *center, center2, *radius, mat,
+            )));
+        }
+        ObjectDesc::Triangle {
+            vertices, normals, ..
+        } => {
+            let tri = match normals {
+                Some(normals) => triangle::Triangle::with_normals(*vertices, *normals, mat),
+                None => triangle::Triangle::new(vertices[0], vertices[1], vertices[2], mat),
+            };
+            parts.push(Arc::new(tri));
+        }
+        ObjectDesc::Mesh {
+            positions,
+            normals,
+            indices,
+            ..
+        } => {
+            if !normals.is_empty() && normals.len() != positions.len() {
+                return Err(invalid(
+                    Some(line),
+                    &field("normals"),
+                    "must be empty or have one entry per position",
+                ));
+            }
+            if indices.is_empty() {
+                return Err(invalid(
+                    Some(line),
+                    &field("indices"),
+                    "needs at least one face",
+                ));
+            }
+            if let Some(face) = indices
+                .iter()
+                .find(|f| f.iter().any(|&v| v >= positions.len()))
+            {
+                return Err(invalid(
+                    Some(line),
+                    &field("indices"),
+                    &format!("face {:?} references a missing position", face),
+                ));
+            }
+            let vertices = triangle::VertexBuffer::new(positions.clone(), normals.clone());
+            parts.push(Arc::new(mesh::Mesh::new(Arc::new(vertices), indices, mat)));
+        }
+        ObjectDesc::Obj { path, .. } => {
+            let model = obj::load(&base_dir.join(path), mat, files)
+                .map_err(|err| invalid(Some(line), &field("path"), &err.to_string()))?;
+            parts.extend(model.objects().iter().cloned());
+        }
+        ObjectDesc::Quad { q, u, v, .. } => {
+            if vec3::cross(*u, *v).near_zero() {
+                return Err(invalid(
+                    Some(line),
+                    &field("v"),
+                    "must not be parallel to u",
+                ));
+            }
+            parts.push(Arc::new(quad::Quad::new(*q, *u, *v, mat)));
+        }
+        ObjectDesc::Box { a, b, .. } => {
+            // Each side is its own object, so the BVH can split them and a box light's
+            // sides are sampled individually.
+            parts.extend(quad::new_box(*a, *b, mat).objects().iter().cloned());
+        }
+        ObjectDesc::Disk {
+            center,
+            normal,
+            radius,
+            ..
+        } => {
+            if normal.near_zero() {
+                return Err(invalid(Some(line), &field("normal"), "must not be zero"));
+            }
+            if *radius <= 0.0 {
+                return Err(invalid(Some(line), &field("radius"), "must be positive"));
+            }
+            parts.push(Arc::new(disk::Disk::new(*center, *normal, *radius, mat)));
+        }
+        ObjectDesc::Plane { point, normal, .. } => {
+            if normal.near_zero() {
+                return Err(invalid(Some(line), &field("normal"), "must not be zero"));
+            }
+            parts.push(Arc::new(plane::Plane::new(*point, *normal, mat)));
+        }
+        ObjectDesc::Instance { .. } | ObjectDesc::Medium { .. } => {
+            return Err(invalid(
+                Some(line),
+                &field("type"),
+                "instances and media can't be used as shapes",
+            ));
+        }
+    }
+    Ok(parts)
+}
+
+fn find_shape<'a>(
+    shapes: &'a HashMap<&str, Shape>,
+    name: &str,
+    line: usize,
+    field: &str,
+) -> Result<&'a Shape, Error> {
+    shapes
+        .get(name)
+        .ok_or_else(|| invalid(Some(line), field, &format!("unknown shape `{}`", name)))
+}
+
+fn instance_of(
+    object: Arc<dyn hittable::Hittable>,
+    to_world: mat4::Mat4,
+    line: usize,
+    index: usize,
+) -> Result<Arc<dyn hittable::Hittable>, Error> {
+    match instance::Instance::new(object, to_world) {
+        Some(instance) => Ok(Arc::new(instance)),
+        None => Err(invalid(
+            Some(line),
+            &format!("objects[{}]", index),
+            "the transform is singular",
+        )),
+    }
+}
+
+fn instance_transform(
+    scale: Option<Scale>,
+    rotate: Option<vec3::Vec3>,
+    translate: Option<vec3::Vec3>,
+    matrix: Option<[[f64; 4]; 4]>,
+) -> Result<mat4::Mat4, &'static str> {
+    // Scale first, then rotate about x, y and z in turn, then translate, then the matrix.
+    let mut m = match scale {
+        Some(Scale::Uniform(s)) => mat4::Mat4::scaling(vec3::Vec3::new(s, s, s)),
+        Some(Scale::Axes(s)) => mat4::Mat4::scaling(s),
+        None => mat4::Mat4::identity(),
+    };
+    if let Some(r) = rotate {
+        for (axis, degrees) in [
+            (vec3::Vec3::new(1.0, 0.0, 0.0), r.x()),
+            (vec3::Vec3::new(0.0, 1.0, 0.0), r.y()),
+            (vec3::Vec3::new(0.0, 0.0, 1.0), r.z()),
+        ] {
+            m = mat4::Mat4::rotation(axis, degrees) * m;
+        }
+    }
+    if let Some(t) = translate {
+        m = mat4::Mat4::translation(t) * m;
+    }
+    if let Some(rows) = matrix {
+        if rows[3] != [0.0, 0.0, 0.0, 1.0] {
+            return Err("must be affine, with a last row of [0, 0, 0, 1]");
+        }
+        m = mat4::Mat4::new(rows) * m;
+    }
+    Ok(m)
+}
+
+fn build_material(
+    desc: &MaterialDesc,
+    textures: &mut TextureSet,
+    line: usize,
+    field: impl Fn(&str) -> String,
+) -> Result<Arc<dyn material::Material>, Error> {
+    Ok(match desc {
+        MaterialDesc::Lambertian { albedo } => {
+            let tex = textures.resolve(albedo, line, &field("albedo"))?;
+            Arc::new(material::Lambertian::from_texture(tex))
+        }
+        MaterialDesc::Metal { albedo, fuzz } => {
+            let tex = textures.resolve(albedo, line, &field("albedo"))?;
+            Arc::new(material::Metal::from_texture(tex, *fuzz))
+        }
+        MaterialDesc::Dielectric {
+            refraction_index,
+            cauchy,
+            sellmeier,
+        } => match (refraction_index, cauchy, sellmeier) {
+            (Some(refraction_index), None, None) => {
+                Arc::new(material::Dielectric::new(*refraction_index))
+            }
+            (None, Some([a, b]), None) => Arc::new(material::Dielectric::dispersive(
+                material::Dispersion::Cauchy { a: *a, b: *b },
+            )),
+            (None, None, Some(SellmeierDesc { b, c })) => Arc::new(
+                material::Dielectric::dispersive(material::Dispersion::Sellmeier { b: *b, c: *c }),
+            ),
+            _ => {
+                return Err(invalid(
+                    Some(line),
+                    &field("refraction_index"),
+                    "needs exactly one of refraction_index, cauchy or sellmeier",
+                ))
+            }
+        },
+        MaterialDesc::DiffuseLight { emit } => {
+            let tex = textures.resolve(emit, line, &field("emit"))?;
+            Arc::new(material::DiffuseLight::from_texture(tex))
+        }
+        MaterialDesc::Isotropic { albedo } => {
+            let tex = textures.resolve(albedo, line, &field("albedo"))?;
+            Arc::new(material::Isotropic::from_texture(tex))
+        }
+        MaterialDesc::Conductor {
+            metal,
+            eta,
+            k,
+            roughness,
+            anisotropy,
+        } => {
+            check_microfacet(*roughness, *anisotropy, line, &field)?;
+            let (eta, k) = match (metal, eta, k) {
+                (Some(metal), None, None) => metal.ior(),
+                (None, Some(eta), Some(k)) => (*eta, *k),
+                _ => {
+                    return Err(invalid(
+                        Some(line),
+                        &field("metal"),
+                        "needs either a metal preset or both eta and k",
+                    ))
+                }
+            };
+            Arc::new(material::Conductor::new(eta, k, *roughness, *anisotropy))
+        }
+        MaterialDesc::RoughDielectric {
+            refraction_index,
+            roughness,
+            anisotropy,
+        } => {
+            check_microfacet(*roughness, *anisotropy, line, &field)?;
+            Arc::new(material::RoughDielectric::new(
+                *refraction_index,
+                *roughness,
+                *anisotropy,
+            ))
+        }
+        MaterialDesc::Principled {
+            base_color,
+            metallic,
+            roughness,
+            specular,
+            clearcoat,
+            clearcoat_roughness,
+            sheen,
+            sheen_tint,
+            transmission,
+            refraction_index,
+            emission,
+        } => {
+            let base_color = match base_color {
+                Some(c) => textures.resolve(c, line, &field("base_color"))?,
+                None => Arc::new(texture::SolidColor::new(color::Color::new(0.8, 0.8, 0.8))),
+            };
+            let mut mat = material::Principled::new(base_color);
+            // Parameters left out keep the material's defaults.
+            let fraction = |name: &str, value: &Option<ScalarOrTexture>| match value {
+                Some(ScalarOrTexture::Value(x)) if !(0.0..=1.0).contains(x) => {
+                    Err(invalid(Some(line), &field(name), "must be between 0 and 1"))
+                }
+                Some(value) => textures.resolve_scalar(value, line, &field(name)).map(Some),
+                None => Ok(None),
+            };
+            if let Some(tex) = fraction("metallic", metallic)? {
+                mat.set_metallic(tex);
+            }
+            if let Some(tex) = fraction("roughness", roughness)? {
+                mat.set_roughness(tex);
+            }
+            if let Some(tex) = fraction("specular", specular)? {
+                mat.set_specular(tex);
+            }
+            if let Some(tex) = fraction("clearcoat", clearcoat)? {
+                mat.set_clearcoat(tex);
+            }
+            if let Some(tex) = fraction("clearcoat_roughness", clearcoat_roughness)? {
+                mat.set_clearcoat_roughness(tex);
+            }
+            if let Some(tex) = fraction("sheen", sheen)? {
+                mat.set_sheen(tex);
+            }
+            if let Some(tex) = fraction("sheen_tint", sheen_tint)? {
+                mat.set_sheen_tint(tex);
+            }
+            if let Some(tex) = fraction("transmission", transmission)? {
+                mat.set_transmission(tex);
+            }
+            if let Some(value) = refraction_index {
+                if let ScalarOrTexture::Value(x) = value {
+                    if *x < 1.0 {
+                        return Err(invalid(
+                            Some(line),
+                            &field("refraction_index"),
+                            "must be at least 1",
+                        ));
+                    }
+                }
+                mat.set_refraction_index(textures.resolve_scalar(
+                    value,
+                    line,
+                    &field("refraction_index"),
+                )?);
+            }
+            if let Some(value) = emission {
+                mat.set_emission(textures.resolve(value, line, &field("emission"))?);
+            }
+            Arc::new(mat)
+        }
+    })
+}
+
+struct TextureSet<'a> {
+    src: &'a str,
+    base_dir: &'a Path,
+    descs: &'a BTreeMap<String, toml::Spanned<TextureDesc>>,
+    built: HashMap<String, Arc<dyn texture::Texture>>,
+    files: Vec<PathBuf>, // Images read so far
+}
+
+impl TextureSet<'_> {
+    fn resolve(
+        &self,
+        value: &ColorOrTexture,
+        line: usize,
+        field: &str,
+    ) -> Result<Arc<dyn texture::Texture>, Error> {
+        match value {
+            ColorOrTexture::Color(c) => Ok(Arc::new(texture::SolidColor::new(*c))),
+            ColorOrTexture::Texture(name) => {
+                self.built.get(name).cloned().ok_or_else(|| {
+                    invalid(Some(line), field, &format!("unknown texture `{}`", name))
+                })
+            }
+        }
+    }
+
+    fn resolve_scalar(
+        &self,
+        value: &ScalarOrTexture,
+        line: usize,
+        field: &str,
+    ) -> Result<Arc<dyn texture::Texture>, Error> {
+        match value {
+            ScalarOrTexture::Value(x) => Ok(Arc::new(texture::SolidColor::new(color::Color::new(
+                *x, *x, *x,
+            )))),
+            ScalarOrTexture::Texture(name) => {
+                self.resolve(&ColorOrTexture::Texture(name.clone()), line, field)
+            }
+        }
+    }
+
+    fn get(
+        &mut self,
+        name: &str,
+        rng: &mut utils::Rng,
+        building: &mut Vec<String>,
+    ) -> Result<Arc<dyn texture::Texture>, Error> {
+        if let Some(tex) = self.built.get(name) {
+            return Ok(tex.clone());
+        }
+        let desc = &self.descs[name];
+        let line = line_of(self.src, desc.span().start);
+        let field = |f: &str| format!("textures.{}.{}", name, f);
+        if building.iter().any(|n| n == name) {
+            return Err(invalid(
+                Some(line),
+                &format!("textures.{}", name),
+                "texture refers back to itself",
+            ));
+        }
+        building.push(name.to_string());
+
+        let tex: Arc<dyn texture::Texture> = match desc.get_ref() {
+            TextureDesc::Solid { color } => Arc::new(texture::SolidColor::new(*color)),
+            TextureDesc::Checker { scale, even, odd } => {
+                let even = self.nested(even, rng, building, line, &field("even"))?;
+                let odd = self.nested(odd, rng, building, line, &field("odd"))?;
+                Arc::new(texture::CheckerTexture::new(*scale, even, odd))
+            }
+            TextureDesc::Image { path } => {
+                self.files.push(self.base_dir.join(path));
+                let image = input::read_image(&self.base_dir.join(path))
+                    .map_err(|err| invalid(Some(line), &field("path"), &err.to_string()))?;
+                Arc::new(texture::ImageTexture::new(image))
+            }
+            TextureDesc::Noise { scale, style } => {
+                Arc::new(texture::NoiseTexture::new(rng, *scale, *style))
+            }
+        };
+
+        building.pop();
+        self.built.insert(name.to_string(), tex.clone());
+        Ok(tex)
+    }
+
+    fn nested(
+        &mut self,
+        value: &ColorOrTexture,
+        rng: &mut utils::Rng,
+        building: &mut Vec<String>,
+        line: usize,
+        field: &str,
+    ) -> Result<Arc<dyn texture::Texture>, Error> {
+        match value {
+            ColorOrTexture::Texture(name) if self.descs.contains_key(name) => {
+                self.get(name, rng, building)
+            }
+            _ => self.resolve(value, line, field),
+        }
+    }
+}
+
+fn check_microfacet(
+    roughness: f64,
+    anisotropy: f64,
+    line: usize,
+    field: impl Fn(&str) -> String,
+) -> Result<(), Error> {
+    if !(0.0..=1.0).contains(&roughness) {
+        return Err(invalid(
+            Some(line),
+            &field("roughness"),
+            "must be between 0 and 1",
+        ));
+    }
+    if !(0.0..=1.0).contains(&anisotropy) {
+        return Err(invalid(
+            Some(line),
+            &field("anisotropy"),
+            "must be between 0 and 1",
+        ));
+    }
+    Ok(())
+}
+
+fn lookup(
+    materials: &HashMap<&str, Arc<dyn material::Material>>,
+    name: &str,
+    line: usize,
+    field: &str,
+) -> Result<Arc<dyn material::Material>, Error> {
+    materials
+        .get(name)
+        .cloned()
+        .ok_or_else(|| invalid(Some(line), field, &format!("unknown material `{}`", name)))
+}
+
+// Where each key of the `[camera]` table is set, for pointing errors at its line.
+#[derive(Deserialize)]
+struct CameraKeys {
+    #[serde(default)]
+    camera: BTreeMap<String, toml::Spanned<de::IgnoredAny>>,
+}
+
+fn check_camera(camera: &CameraSettings, src: &str) -> Result<(), Error> {
+    let keys: CameraKeys = toml::from_str(src)?;
+    let fail = |key: &str, message: &str| -> Result<(), Error> {
+        let line = keys.camera.get(key).map(|v| line_of(src, v.span().start));
+        Err(invalid(line, &format!("camera.{}", key), message))
+    };
+
+    if camera.image_width < 1 {
+        return fail("image_width", "must be at least 1");
+    }
+    if camera.samples_per_pixel < 1 {
+        return fail("samples_per_pixel", "must be at least 1");
+    }
+    if camera.pass_samples < 1 {
+        return fail("pass_samples", "must be at least 1");
+    }
+    if camera.min_samples < 1 {
+        return fail("min_samples", "must be at least 1");
+    }
+    if camera.noise_threshold < 0.0 {
+        return fail("noise_threshold", "must not be negative");
+    }
+    if camera.max_depth < 1 {
+        return fail("max_depth", "must be at least 1");
+    }
+    if camera.aspect_ratio <= 0.0 {
+        return fail("aspect_ratio", "must be positive");
+    }
+    if camera.focus_dist <= 0.0 {
+        return fail("focus_dist", "must be positive");
+    }
+    // Any of these would leave the camera's view vectors NaN.
+    if !(camera.vfov > 0.0 && camera.vfov < 180.0) {
+        return fail("vfov", "must be between 0 and 180");
+    }
+    let view = camera.lookat - camera.lookfrom;
+    if view.length_squared() == 0.0 {
+        return fail("lookat", "must differ from lookfrom");
+    }
+    if vec3::cross(camera.vup, view).length_squared() == 0.0 {
+        return fail("vup", "must not be parallel to the view direction");
+    }
+    Ok(())
+}
+
+fn invalid(line: Option<usize>, field: &str, message: &str) -> Error {
+    Error::Invalid {
+        line,
+        field: field.to_string(),
+        message: message.to_string(),
+    }
+}
+
+fn line_of(src: &str, offset: usize) -> usize {
+    src[..offset].matches('\n').count() + 1
+}
+
+fn final_scene(rng: &mut utils::Rng) -> Scene {
+    // World
+    let mut world = hittable_list::HittableList::new();
+
+    let ground_material = Arc::new(material::Lambertian::new(color::Color::new(0.5, 0.5, 0.5)));
+    world.add(Arc::new(sphere::Sphere::new(
+        vec3::Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        ground_material,
+    )));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = utils::random_double(rng);
+            let center = vec3::Point3::new(
+                (a as f64) + 0.9 * utils::random_double(rng),
+                0.2,
+                (b as f64) + 0.9 * utils::random_double(rng),
+            );
+
+            if (center - vec3::Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                if choose_mat < 0.8 {
+                    // diffuse
+                    let albedo = color::Color::new_random(rng) * color::Color::new_random(rng);
+                    let sphere_material = Arc::new(material::Lambertian::new(albedo));
+                    world.add(Arc::new(sphere::Sphere::new(center, 0.2, sphere_material)));
+                } else if choose_mat < 0.95 {
+                    // metal
+                    let albedo = color::Color::new_random_bounded(rng, 0.5, 1.0);
+                    let fuzz = utils::random_double_bounded(rng, 0.0, 0.5);
+                    let sphere_material = Arc::new(material::Metal::new(albedo, fuzz));
+                    world.add(Arc::new(sphere::Sphere::new(center, 0.2, sphere_material)));
+                } else {
+                    // glass
+                    let sphere_material = Arc::new(material::Dielectric::new(1.5));
+                    world.add(Arc::new(sphere::Sphere::new(center, 0.2, sphere_material)));
+                }
+            }
+        }
+    }
+
+    let material_1 = Arc::new(material::Dielectric::new(1.5));
+    world.add(Arc::new(sphere::Sphere::new(
+        vec3::Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        material_1,
+    )));
+
+    let material_2 = Arc::new(material::Lambertian::new(color::Color::new(0.4, 0.2, 0.1)));
+    world.add(Arc::new(sphere::Sphere::new(
+        vec3::Point3::new(-4.0, 1.0, 0.0),
+        1.0,
+        material_2,
+    )));
+
+    let material_3 = Arc::new(material::Metal::new(color::Color::new(0.7, 0.6, 0.5), 0.0));
+    world.add(Arc::new(sphere::Sphere::new(
+        vec3::Point3::new(4.0, 1.0, 0.0),
+        1.0,
+        material_3,
+    )));
+
+    Scene {
+        camera: CameraSettings::default(),
+        world,
+        lights: hittable_list::HittableList::new(),
+        source: hash_text("final"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(src: &str) -> String {
+        let mut rng = utils::Rng::new(1);
+        match parse(src, Path::new(""), &mut rng) {
+            Ok(_) => panic!("scene parsed without an error"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    const SPHERE: &str = "
+[materials.white]
+type = \"lambertian\"
+albedo = [0.5, 0.5, 0.5]
+
+[[objects]]
+type = \"sphere\"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = \"white\"
+";
+
+    #[test]
+    fn unknown_field_reports_its_line() {
+        let err = parse_error(&format!(
+            "[camera]\nimage_width = 10\nfov = 40.0\n{}",
+            SPHERE
+        ));
+        assert!(err.contains("line 3"), "{}", err);
+        assert!(err.contains("fov"), "{}", err);
+    }
+
+    #[test]
+    fn wrong_type_reports_its_line() {
+        let err = parse_error(&format!("[camera]\n\nimage_width = \"wide\"\n{}", SPHERE));
+        assert!(err.contains("line 3"), "{}", err);
+    }
+
+    #[test]
+    fn out_of_range_camera_setting_reports_its_line() {
+        let err = parse_error(&format!(
+            "[camera]\nimage_width = 10\nvfov = 180.0\n{}",
+            SPHERE
+        ));
+        assert_eq!(
+            err,
+            "invalid scene file at line 3: camera.vfov: must be between 0 and 180"
+        );
+    }
+
+    #[test]
+    fn degenerate_view_is_rejected() {
+        let err = parse_error(&format!(
+            "[camera]\nlookfrom = [1.0, 2.0, 3.0]\nlookat = [1.0, 2.0, 3.0]\n{}",
+            SPHERE
+        ));
+        assert!(err.contains("line 3: camera.lookat"), "{}", err);
+
+        let err = parse_error(&format!(
+            "[camera]\nlookfrom = [0.0, 5.0, 0.0]\nlookat = [0.0, 0.0, 0.0]\n{}",
+            SPHERE
+        ));
+        assert!(err.contains("camera.vup"), "{}", err);
+    }
+
+    #[test]
+    fn out_of_range_object_setting_reports_its_line() {
+        let disk = "
+[[objects]]
+type = \"disk\"
+center = [0.0, 0.0, 0.0]
+normal = [0.0, 1.0, 0.0]
+radius = -1.0
+material = \"white\"
+";
+        let err = parse_error(&format!("{}{}", SPHERE, disk));
+        assert_eq!(
+            err,
+            "invalid scene file at line 12: objects[1].radius: must be positive"
+        );
+    }
+
+    #[test]
+    fn editing_a_model_changes_the_scene_source() {
+        let dir = std::env::temp_dir().join(format!("scene-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let model = dir.join("triangle.obj");
+        let src = "[[objects]]\ntype = \"obj\"\npath = \"triangle.obj\"\n";
+        let source = || {
+            let mut rng = utils::Rng::new(1);
+            parse(src, &dir, &mut rng).unwrap().source
+        };
+
+        fs::write(&model, "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        let before = source();
+        assert_eq!(before, source());
+        fs::write(&model, "v 0 0 0\nv 2 0 0\nv 0 2 0\nf 1 2 3\n# scaled\n").unwrap();
+        let after = source();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_ne!(before, after);
+    }
+}
@@ -0,0 +1,204 @@
+use std::sync::OnceLock;
+
+use crate::{color, vec3};
+
+// Spectral rendering traces each path at a few wavelengths instead of in RGB. Material colors
+// stay RGB and are upsampled to a smooth spectrum wherever a path meets them; the radiance a
+// path carries back is projected onto the CIE color matching functions and converted from
+// XYZ to linear sRGB.
+
+const LAMBDA_MIN: f64 = 360.0;
+const LAMBDA_MAX: f64 = 830.0;
+
+// Wavelength, in nanometers, of the helium d line that glass catalogs quote refractive
+// indices at. Dispersive materials use it when rendering in RGB.
+pub const REFERENCE_WAVELENGTH: f64 = 587.56;
+
+// Number of wavelengths a path carries, one per channel of the color::Color that holds its
+// spectral values.
+const SAMPLES: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct Wavelengths {
+    lambda: [f64; SAMPLES],
+    pdf: [f64; SAMPLES],
+}
+
+impl Wavelengths {
+    pub fn sample(u: f64) -> Self {
+        // Hero wavelength sampling: the other wavelengths are rotated evenly through the
+        // sampling distribution, so each path covers the whole visible range.
+        let mut lambda = [0.0; SAMPLES];
+        let mut pdf = [0.0; SAMPLES];
+        for i in 0..SAMPLES {
+            let ui = (u + i as f64 / SAMPLES as f64).fract();
+            lambda[i] = sample_visible(ui);
+            pdf[i] = visible_pdf(lambda[i]);
+        }
+        Self { lambda, pdf }
+    }
+
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    pub fn terminate_secondary(&mut self) {
+        // Once the path's direction depends on wavelength, only the hero wavelength can follow
+        // it. The hero then stands in for all of them, so its density drops accordingly.
+        if self.pdf[1] == 0.0 {
+            return;
+        }
+        self.pdf[0] /= SAMPLES as f64;
+        for pdf in &mut self.pdf[1..] {
+            *pdf = 0.0;
+        }
+    }
+
+    pub fn upsample(&self, rgb: color::Color) -> color::Color {
+        // Value of the spectrum for the RGB color at each wavelength.
+        let [a, b, c] = self.lambda.map(|lambda| rgb_to_spectrum(rgb, lambda));
+        color::Color::new(a, b, c)
+    }
+
+    pub fn to_rgb(&self, radiance: color::Color) -> color::Color {
+        // Monte Carlo estimate of the radiance's XYZ color from its value at each wavelength.
+        let values = [radiance.x(), radiance.y(), radiance.z()];
+        let mut xyz = vec3::Vec3::default();
+        for ((value, lambda), pdf) in values.into_iter().zip(self.lambda).zip(self.pdf) {
+            if pdf > 0.0 {
+                xyz += value * color_matching(lambda) / pdf;
+            }
+        }
+        let rgb = xyz_to_rgb(xyz / SAMPLES as f64);
+        let white = white_balance();
+        color::Color::new(
+            rgb.x() / white.x(),
+            rgb.y() / white.y(),
+            rgb.z() / white.z(),
+        )
+    }
+}
+
+// Sampling density over the visible wavelengths from pbrt, roughly following the luminous
+// efficiency curve so that wavelengths the eye barely sees are rarely traced.
+const VISIBLE_FALLOFF: f64 = 0.0072;
+const VISIBLE_PEAK: f64 = 538.0;
+
+fn visible_pdf(lambda: f64) -> f64 {
+    let lo = (VISIBLE_FALLOFF * (VISIBLE_PEAK - LAMBDA_MIN)).tanh();
+    let hi = (VISIBLE_FALLOFF * (LAMBDA_MAX - VISIBLE_PEAK)).tanh();
+    let cosh = (VISIBLE_FALLOFF * (lambda - VISIBLE_PEAK)).cosh();
+    VISIBLE_FALLOFF / ((lo + hi) * cosh * cosh)
+}
+
+fn sample_visible(u: f64) -> f64 {
+    let lo = (VISIBLE_FALLOFF * (VISIBLE_PEAK - LAMBDA_MIN)).tanh();
+    let hi = (VISIBLE_FALLOFF * (LAMBDA_MAX - VISIBLE_PEAK)).tanh();
+    VISIBLE_PEAK + (u * (lo + hi) - lo).atanh() / VISIBLE_FALLOFF
+}
+
+// Smits' basis spectra for RGB upsampling ("An RGB-to-Spectrum Conversion for Reflectances",
+// 1999), sampled at the centers of ten bins from 380 to 720 nm.
+const SMITS_START: f64 = 380.0;
+const SMITS_BIN: f64 = 34.0;
+const SMITS_WHITE: [f64; 10] = [
+    1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+];
+const SMITS_CYAN: [f64; 10] = [
+    0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+];
+const SMITS_MAGENTA: [f64; 10] = [
+    1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+];
+const SMITS_YELLOW: [f64; 10] = [
+    0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const SMITS_RED: [f64; 10] = [
+    0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const SMITS_GREEN: [f64; 10] = [
+    0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+];
+const SMITS_BLUE: [f64; 10] = [
+    1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+fn smits(basis: &[f64; 10], lambda: f64) -> f64 {
+    let t = ((lambda - SMITS_START) / SMITS_BIN - 0.5).clamp(0.0, 9.0);
+    let i = (t as usize).min(8);
+    let f = t - i as f64;
+    basis[i] * (1.0 - f) + basis[i + 1] * f
+}
+
+fn rgb_to_spectrum(rgb: color::Color, lambda: f64) -> f64 {
+    // Splits the color into white, the secondary color made of the two largest components and
+    // the primary left over, and adds up their basis spectra. Scaling is linear, so emission
+    // brighter than 1 upsamples the same way.
+    let (r, g, b) = (rgb.x(), rgb.y(), rgb.z());
+    let basis = |spectrum| smits(spectrum, lambda);
+    if r <= g && r <= b {
+        r * basis(&SMITS_WHITE)
+            + if g <= b {
+                (g - r) * basis(&SMITS_CYAN) + (b - g) * basis(&SMITS_BLUE)
+            } else {
+                (b - r) * basis(&SMITS_CYAN) + (g - b) * basis(&SMITS_GREEN)
+            }
+    } else if g <= r && g <= b {
+        g * basis(&SMITS_WHITE)
+            + if r <= b {
+                (r - g) * basis(&SMITS_MAGENTA) + (b - r) * basis(&SMITS_BLUE)
+            } else {
+                (b - g) * basis(&SMITS_MAGENTA) + (r - b) * basis(&SMITS_RED)
+            }
+    } else {
+        b * basis(&SMITS_WHITE)
+            + if r <= g {
+                (r - b) * basis(&SMITS_YELLOW) + (g - r) * basis(&SMITS_GREEN)
+            } else {
+                (g - b) * basis(&SMITS_YELLOW) + (r - g) * basis(&SMITS_RED)
+            }
+    }
+}
+
+fn color_matching(lambda: f64) -> vec3::Vec3 {
+    // The CIE 1931 2-degree observer as the multi-lobe Gaussian fit of Wyman, Sloan and
+    // Shirley, "Simple Analytic Approximations to the CIE XYZ Color Matching Functions".
+    let g = |mu: f64, sigma_below: f64, sigma_above: f64| {
+        let sigma = if lambda < mu {
+            sigma_below
+        } else {
+            sigma_above
+        };
+        let t = (lambda - mu) / sigma;
+        (-0.5 * t * t).exp()
+    };
+    vec3::Vec3::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+fn xyz_to_rgb(xyz: vec3::Vec3) -> color::Color {
+    // Linear sRGB primaries.
+    color::Color::new(
+        3.2404542 * xyz.x() - 1.5371385 * xyz.y() - 0.4985314 * xyz.z(),
+        -0.9692660 * xyz.x() + 1.8760108 * xyz.y() + 0.0415560 * xyz.z(),
+        0.0556434 * xyz.x() - 0.2040259 * xyz.y() + 1.0572252 * xyz.z(),
+    )
+}
+
+fn white_balance() -> color::Color {
+    // The color a constant spectrum of 1 comes out as, which every result is divided by so
+    // that it maps to RGB white, matching the equal-energy white Smits' spectra assume.
+    static WHITE: OnceLock<color::Color> = OnceLock::new();
+    *WHITE.get_or_init(|| {
+        let mut xyz = vec3::Vec3::default();
+        let mut lambda = LAMBDA_MIN;
+        while lambda <= LAMBDA_MAX {
+            xyz += color_matching(lambda);
+            lambda += 1.0;
+        }
+        xyz_to_rgb(xyz)
+    })
+}
@@ -1,26 +1,43 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::{hittable, interval, material, ray, vec3};
+use crate::{aabb, hittable, interval, material, onb, ray, utils, vec3};
 
 pub struct Sphere {
-    center: vec3::Point3,
+    center: ray::Ray, // Center at time 0, moving linearly to origin + direction at time 1
     radius: f64,
-    mat: Rc<dyn material::Material>,
+    mat: Arc<dyn material::Material>,
+    bbox: aabb::Aabb,
 }
 
 impl Sphere {
-    pub fn new(center: vec3::Point3, radius: f64, mat: Rc<dyn material::Material>) -> Self {
+    pub fn new(center: vec3::Point3, radius: f64, mat: Arc<dyn material::Material>) -> Self {
+        Self::new_moving(center, center, radius, mat)
+    }
+
+    pub fn new_moving(
+        center1: vec3::Point3,
+        center2: vec3::Point3,
+        radius: f64,
+        mat: Arc<dyn material::Material>,
+    ) -> Self {
+        let radius = radius.max(0.0);
+        let rvec = vec3::Vec3::new(radius, radius, radius);
+        // The box has to cover the sphere along its whole path, not just at one end.
+        let box1 = aabb::Aabb::from_points(center1 - rvec, center1 + rvec);
+        let box2 = aabb::Aabb::from_points(center2 - rvec, center2 + rvec);
         Self {
-            center,
-            radius: radius.max(0.0),
+            center: ray::Ray::new(center1, center2 - center1),
+            radius,
             mat,
+            bbox: aabb::Aabb::surrounding(box1, box2),
         }
     }
 }
 
 impl hittable::Hittable for Sphere {
     fn hit(&self, r: ray::Ray, ray_t: interval::Interval, rec: &mut hittable::HitRecord) -> bool {
-        let oc = self.center - r.origin();
+        let current_center = self.center.at(r.time());
+        let oc = current_center - r.origin();
         let a = r.direction().length_squared();
         let h = vec3::dot(r.direction(), oc);
         let c = oc.length_squared() - self.radius * self.radius;
@@ -43,10 +60,55 @@ impl hittable::Hittable for Sphere {
 
         rec.set_t(root);
         rec.set_p(r.at(rec.t()));
-        let outward_normal = (rec.p() - self.center) / self.radius;
+        let outward_normal = (rec.p() - current_center) / self.radius;
         rec.set_face_normal(r, outward_normal);
+        let (u, v) = get_sphere_uv(outward_normal);
+        rec.set_uv(u, v);
         rec.set_mat(self.mat.clone());
 
         true
     }
+
+    fn bounding_box(&self) -> aabb::Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: vec3::Point3, direction: vec3::Vec3) -> f64 {
+        // Uniform over the cone the sphere subtends; moving spheres are sampled where they
+        // are at time 0.
+        let mut rec = hittable::HitRecord::default();
+        if !self.hit(
+            ray::Ray::new(origin, direction),
+            interval::Interval::new(0.001, f64::INFINITY),
+            &mut rec,
+        ) {
+            return 0.0;
+        }
+
+        let dist_squared = (self.center.at(0.0) - origin).length_squared();
+        let cos_theta_max = (1.0 - self.radius * self.radius / dist_squared)
+            .max(0.0)
+            .sqrt();
+        let solid_angle = 2.0 * utils::PI * (1.0 - cos_theta_max);
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: vec3::Point3, u: (f64, f64)) -> vec3::Vec3 {
+        let direction = self.center.at(0.0) - origin;
+        let distance_squared = direction.length_squared();
+        let uvw = onb::Onb::new(direction);
+        uvw.transform(vec3::sample_to_sphere(u, self.radius, distance_squared))
+    }
+}
+
+fn get_sphere_uv(p: vec3::Point3) -> (f64, f64) {
+    // p: a given point on the sphere of radius one, centered at the origin.
+    // u: returned value [0,1] of angle around the Y axis from X=-1.
+    // v: returned value [0,1] of angle from Y=-1 to Y=+1.
+    //     <1 0 0> yields <0.50 0.50>       <-1  0  0> yields <0.00 0.50>
+    //     <0 1 0> yields <0.50 1.00>       < 0 -1  0> yields <0.50 0.00>
+    //     <0 0 1> yields <0.25 0.50>       < 0  0 -1> yields <0.75 0.50>
+    let theta = (-p.y()).acos();
+    let phi = (-p.z()).atan2(p.x()) + utils::PI;
+    (phi / (2.0 * utils::PI), theta / utils::PI)
 }
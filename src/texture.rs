@@ -0,0 +1,131 @@
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::{color, framebuffer, perlin, utils, vec3};
+
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, p: vec3::Point3) -> color::Color;
+}
+
+pub struct SolidColor {
+    albedo: color::Color,
+}
+
+impl SolidColor {
+    pub fn new(albedo: color::Color) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: vec3::Point3) -> color::Color {
+        self.albedo
+    }
+}
+
+// Alternates between two textures in a 3D grid of cubes `scale` units wide, so the pattern
+// follows the hit point rather than the surface coordinates.
+pub struct CheckerTexture {
+    inv_scale: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        Self {
+            inv_scale: 1.0 / scale,
+            even,
+            odd,
+        }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: vec3::Point3) -> color::Color {
+        let x_integer = (self.inv_scale * p.x()).floor() as i64;
+        let y_integer = (self.inv_scale * p.y()).floor() as i64;
+        let z_integer = (self.inv_scale * p.z()).floor() as i64;
+
+        let is_even = (x_integer + y_integer + z_integer) % 2 == 0;
+
+        if is_even {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+pub struct ImageTexture {
+    image: framebuffer::Framebuffer,
+}
+
+impl ImageTexture {
+    pub fn new(image: framebuffer::Framebuffer) -> Self {
+        Self { image }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: vec3::Point3) -> color::Color {
+        let (width, height) = (self.image.width(), self.image.height());
+        // If we have no texture data, then return solid cyan as a debugging aid.
+        if width == 0 || height == 0 {
+            return color::Color::new(0.0, 1.0, 1.0);
+        }
+
+        // Clamp input texture coordinates to [0,1] x [1,0], flipping v to image coordinates,
+        // then filter bilinearly between the four nearest texel centers.
+        let u = u.clamp(0.0, 1.0);
+        let v = 1.0 - v.clamp(0.0, 1.0);
+        let x = (u * width as f64 - 0.5).clamp(0.0, (width - 1) as f64);
+        let y = (v * height as f64 - 0.5).clamp(0.0, (height - 1) as f64);
+
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+        let (tx, ty) = (x - x0 as f64, y - y0 as f64);
+
+        let top = (1.0 - tx) * self.image.pixel(x0, y0) + tx * self.image.pixel(x1, y0);
+        let bottom = (1.0 - tx) * self.image.pixel(x0, y1) + tx * self.image.pixel(x1, y1);
+        (1.0 - ty) * top + ty * bottom
+    }
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoiseStyle {
+    Smooth,     // Raw Perlin noise
+    Turbulence, // Summed octaves of noise
+    Marble,     // Sine stripes along z, phase-shifted by turbulence
+}
+
+pub struct NoiseTexture {
+    noise: perlin::Perlin,
+    scale: f64,
+    style: NoiseStyle,
+}
+
+impl NoiseTexture {
+    pub fn new(rng: &mut utils::Rng, scale: f64, style: NoiseStyle) -> Self {
+        Self {
+            noise: perlin::Perlin::new(rng),
+            scale,
+            style,
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: vec3::Point3) -> color::Color {
+        let grey = match self.style {
+            NoiseStyle::Smooth => 0.5 * (1.0 + self.noise.noise(self.scale * p)),
+            NoiseStyle::Turbulence => self.noise.turb(self.scale * p, 7),
+            NoiseStyle::Marble => {
+                0.5 * (1.0 + (self.scale * p.z() + 10.0 * self.noise.turb(p, 7)).sin())
+            }
+        };
+        color::Color::new(1.0, 1.0, 1.0) * grey
+    }
+}
@@ -0,0 +1,332 @@
+use std::sync::Arc;
+
+use crate::{aabb, hittable, interval, material, ray, vec3};
+
+// Vertex data shared by every triangle of a mesh. Normals are optional: leave them empty for
+// flat shading, or give one per position to shade smoothly across faces. Texture coordinates
+// work the same way; without them a hit's (u, v) are its barycentric coordinates.
+pub struct VertexBuffer {
+    positions: Vec<vec3::Point3>,
+    normals: Vec<vec3::Vec3>,
+    texcoords: Vec<(f64, f64)>,
+}
+
+impl VertexBuffer {
+    pub fn new(positions: Vec<vec3::Point3>, normals: Vec<vec3::Vec3>) -> Self {
+        assert!(
+            normals.is_empty() || normals.len() == positions.len(),
+            "vertex normals must be empty or match the number of positions"
+        );
+        let normals = normals.into_iter().map(vec3::unit_vector).collect();
+        Self {
+            positions,
+            normals,
+            texcoords: Vec::new(),
+        }
+    }
+
+    pub fn set_texcoords(&mut self, texcoords: Vec<(f64, f64)>) {
+        assert!(
+            texcoords.is_empty() || texcoords.len() == self.positions.len(),
+            "texture coordinates must be empty or match the number of positions"
+        );
+        self.texcoords = texcoords;
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+}
+
+pub struct Triangle {
+    vertices: Arc<VertexBuffer>,
+    indices: [usize; 3],
+    mat: Arc<dyn material::Material>,
+    bbox: aabb::Aabb,
+}
+
+impl Triangle {
+    pub fn new(
+        a: vec3::Point3,
+        b: vec3::Point3,
+        c: vec3::Point3,
+        mat: Arc<dyn material::Material>,
+    ) -> Self {
+        let vertices = VertexBuffer::new(vec![a, b, c], Vec::new());
+        Self::from_buffer(Arc::new(vertices), [0, 1, 2], mat)
+    }
+
+    pub fn with_normals(
+        positions: [vec3::Point3; 3],
+        normals: [vec3::Vec3; 3],
+        mat: Arc<dyn material::Material>,
+    ) -> Self {
+        let vertices = VertexBuffer::new(positions.to_vec(), normals.to_vec());
+        Self::from_buffer(Arc::new(vertices), [0, 1, 2], mat)
+    }
+
+    pub fn from_buffer(
+        vertices: Arc<VertexBuffer>,
+        indices: [usize; 3],
+        mat: Arc<dyn material::Material>,
+    ) -> Self {
+        let [p0, p1, p2] = indices.map(|i| vertices.positions[i]);
+        let bbox = aabb::Aabb::surrounding(
+            aabb::Aabb::from_points(p0, p1),
+            aabb::Aabb::from_points(p2, p2),
+        )
+        .padded();
+        Self {
+            vertices,
+            indices,
+            mat,
+            bbox,
+        }
+    }
+
+    pub fn area(&self) -> f64 {
+        let [p0, p1, p2] = self.indices.map(|i| self.vertices.positions[i]);
+        0.5 * vec3::cross(p1 - p0, p2 - p0).length()
+    }
+
+    fn shading_normal(&self, (u, v): (f64, f64)) -> vec3::Vec3 {
+        let [n0, n1, n2] = self.indices.map(|i| self.vertices.normals[i]);
+        vec3::unit_vector((1.0 - u - v) * n0 + u * n1 + v * n2)
+    }
+
+    fn surface_uv(&self, (u, v): (f64, f64)) -> (f64, f64) {
+        if self.vertices.texcoords.is_empty() {
+            return (u, v);
+        }
+        let [t0, t1, t2] = self.indices.map(|i| self.vertices.texcoords[i]);
+        let w = 1.0 - u - v;
+        (
+            w * t0.0 + u * t1.0 + v * t2.0,
+            w * t0.1 + u * t1.1 + v * t2.1,
+        )
+    }
+}
+
+impl hittable::Hittable for Triangle {
+    fn hit(&self, r: ray::Ray, ray_t: interval::Interval, rec: &mut hittable::HitRecord) -> bool {
+        // Möller–Trumbore: solve for the ray parameter and the barycentric weights of the
+        // second and third vertices directly, without intersecting the supporting plane first.
+        let [p0, p1, p2] = self.indices.map(|i| self.vertices.positions[i]);
+        let edge1 = p1 - p0;
+        let edge2 = p2 - p0;
+
+        let pvec = vec3::cross(r.direction(), edge2);
+        let det = vec3::dot(edge1, pvec);
+        if det.abs() < 1e-12 {
+            // The ray is parallel to the triangle's plane.
+            return false;
+        }
+        let inv_det = 1.0 / det;
+
+        let tvec = r.origin() - p0;
+        let u = vec3::dot(tvec, pvec) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return false;
+        }
+
+        let qvec = vec3::cross(tvec, edge1);
+        let v = vec3::dot(r.direction(), qvec) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return false;
+        }
+
+        let t = vec3::dot(edge2, qvec) * inv_det;
+        if !ray_t.surrounds(t) {
+            return false;
+        }
+
+        rec.set_t(t);
+        rec.set_p(r.at(t));
+        rec.set_barycentric(u, v);
+        let (tex_u, tex_v) = self.surface_uv((u, v));
+        rec.set_uv(tex_u, tex_v);
+        let geometric_normal = vec3::unit_vector(vec3::cross(edge1, edge2));
+        rec.set_face_normal(r, geometric_normal);
+        if !self.vertices.normals.is_empty() {
+            // Keep the interpolated normal on the same side as the geometric one so front_face
+            // stays consistent with the actual surface.
+            let mut n = self.shading_normal(rec.barycentric());
+            if vec3::dot(n, geometric_normal) < 0.0 {
+                n = -n;
+            }
+            rec.set_shading_normal(n);
+        }
+        rec.set_mat(self.mat.clone());
+
+        true
+    }
+
+    fn bounding_box(&self) -> aabb::Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: vec3::Point3, direction: vec3::Vec3) -> f64 {
+        // random() is uniform over the triangle's area; converting that to solid angle divides
+        // by the cosine at the light and multiplies by the squared distance.
+        let mut rec = hittable::HitRecord::default();
+        let r = ray::Ray::new(origin, direction);
+        if !self.hit(r, interval::Interval::new(0.001, f64::INFINITY), &mut rec) {
+            return 0.0;
+        }
+
+        let [p0, p1, p2] = self.indices.map(|i| self.vertices.positions[i]);
+        let normal = vec3::unit_vector(vec3::cross(p1 - p0, p2 - p0));
+        let distance_squared = rec.t() * rec.t() * direction.length_squared();
+        let cosine = (vec3::dot(direction, normal) / direction.length()).abs();
+        if cosine < 1e-8 {
+            return 0.0;
+        }
+        distance_squared / (cosine * self.area())
+    }
+
+    fn random(&self, origin: vec3::Point3, (mut a, mut b): (f64, f64)) -> vec3::Vec3 {
+        // Folding the unit square onto the triangle keeps points uniform over its area.
+        let [p0, p1, p2] = self.indices.map(|i| self.vertices.positions[i]);
+        if a + b > 1.0 {
+            a = 1.0 - a;
+            b = 1.0 - b;
+        }
+        p0 + a * (p1 - p0) + b * (p2 - p0) - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+
+    fn mat() -> Arc<dyn material::Material> {
+        Arc::new(material::Lambertian::default())
+    }
+
+    // The unit right triangle in the z = 0 plane, facing +z.
+    fn unit_triangle() -> Triangle {
+        Triangle::new(
+            vec3::Point3::new(0.0, 0.0, 0.0),
+            vec3::Point3::new(1.0, 0.0, 0.0),
+            vec3::Point3::new(0.0, 1.0, 0.0),
+            mat(),
+        )
+    }
+
+    // Shoots a ray straight down at (x, y) from above the plane.
+    fn hit_from_above(tri: &Triangle, x: f64, y: f64) -> Option<hittable::HitRecord> {
+        let r = ray::Ray::new(
+            vec3::Point3::new(x, y, 1.0),
+            vec3::Vec3::new(0.0, 0.0, -1.0),
+        );
+        let mut rec = hittable::HitRecord::default();
+        tri.hit(r, interval::Interval::new(0.001, f64::INFINITY), &mut rec)
+            .then_some(rec)
+    }
+
+    fn assert_close(a: vec3::Vec3, b: vec3::Vec3) {
+        assert!((a - b).length() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn interior_hit() {
+        let rec = hit_from_above(&unit_triangle(), 0.25, 0.5).unwrap();
+        assert!((rec.t() - 1.0).abs() < 1e-12);
+        assert_close(rec.p(), vec3::Point3::new(0.25, 0.5, 0.0));
+        assert_eq!(rec.barycentric(), (0.25, 0.5));
+        assert_eq!(rec.uv(), (0.25, 0.5));
+        assert!(rec.front_face());
+        assert_close(rec.normal(), vec3::Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn edges_and_vertices_are_hit() {
+        let tri = unit_triangle();
+        for (x, y) in [
+            (0.5, 0.0),
+            (0.0, 0.5),
+            (0.5, 0.5),
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (0.0, 1.0),
+        ] {
+            assert!(
+                hit_from_above(&tri, x, y).is_some(),
+                "missed ({}, {})",
+                x,
+                y
+            );
+        }
+    }
+
+    #[test]
+    fn points_outside_are_missed() {
+        let tri = unit_triangle();
+        for (x, y) in [(0.6, 0.6), (-0.1, 0.5), (0.5, -0.1), (1.1, 0.0)] {
+            assert!(hit_from_above(&tri, x, y).is_none(), "hit ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn parallel_ray_is_missed() {
+        let tri = unit_triangle();
+        let mut rec = hittable::HitRecord::default();
+        for origin in [
+            vec3::Point3::new(-1.0, 0.25, 0.0),
+            vec3::Point3::new(-1.0, 0.25, 0.5),
+        ] {
+            let r = ray::Ray::new(origin, vec3::Vec3::new(1.0, 0.0, 0.0));
+            assert!(!tri.hit(r, interval::UNIVERSE, &mut rec));
+        }
+    }
+
+    #[test]
+    fn back_face_is_hit_with_the_normal_flipped() {
+        let r = ray::Ray::new(
+            vec3::Point3::new(0.25, 0.25, -2.0),
+            vec3::Vec3::new(0.0, 0.0, 1.0),
+        );
+        let mut rec = hittable::HitRecord::default();
+        assert!(unit_triangle().hit(r, interval::Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert!((rec.t() - 2.0).abs() < 1e-12);
+        assert!(!rec.front_face());
+        assert_close(rec.normal(), vec3::Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn hit_outside_the_interval_is_missed() {
+        let r = ray::Ray::new(
+            vec3::Point3::new(0.25, 0.25, 1.0),
+            vec3::Vec3::new(0.0, 0.0, -1.0),
+        );
+        let mut rec = hittable::HitRecord::default();
+        assert!(!unit_triangle().hit(r, interval::Interval::new(0.001, 0.5), &mut rec));
+    }
+
+    #[test]
+    fn texture_coordinates_and_normals_are_interpolated() {
+        let normals = [
+            vec3::Vec3::new(0.0, 0.0, 1.0),
+            vec3::Vec3::new(1.0, 0.0, 1.0),
+            vec3::Vec3::new(0.0, 1.0, 1.0),
+        ];
+        let mut vertices = VertexBuffer::new(
+            vec![
+                vec3::Point3::new(0.0, 0.0, 0.0),
+                vec3::Point3::new(1.0, 0.0, 0.0),
+                vec3::Point3::new(0.0, 1.0, 0.0),
+            ],
+            normals.to_vec(),
+        );
+        vertices.set_texcoords(vec![(0.5, 0.5), (1.0, 0.5), (0.5, 1.0)]);
+        let tri = Triangle::from_buffer(Arc::new(vertices), [0, 1, 2], mat());
+
+        let rec = hit_from_above(&tri, 0.25, 0.5).unwrap();
+        let (u, v) = rec.uv();
+        assert!((u - 0.625).abs() < 1e-12 && (v - 0.75).abs() < 1e-12);
+        let [n0, n1, n2] = normals.map(vec3::unit_vector);
+        let expected = vec3::unit_vector(0.25 * n0 + 0.25 * n1 + 0.5 * n2);
+        assert_close(rec.normal(), expected);
+    }
+}
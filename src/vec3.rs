@@ -5,9 +5,12 @@ use std::ops::Mul;
 use std::ops::Neg;
 use std::ops::Sub;
 
+use serde::Deserialize;
+
 use crate::utils;
 
-#[derive(Debug, Copy, Clone, Default)]
+#[derive(Debug, Copy, Clone, Default, Deserialize)]
+#[serde(from = "[f64; 3]")]
 pub struct Vec3(f64, f64, f64);
 
 pub type Point3 = Vec3;
@@ -17,19 +20,19 @@ impl Vec3 {
         Vec3(x, y, z)
     }
 
-    pub fn new_random() -> Self {
+    pub fn new_random(rng: &mut utils::Rng) -> Self {
         Vec3(
-            utils::random_double(),
-            utils::random_double(),
-            utils::random_double(),
+            utils::random_double(rng),
+            utils::random_double(rng),
+            utils::random_double(rng),
         )
     }
 
-    pub fn new_random_bounded(min: f64, max: f64) -> Self {
+    pub fn new_random_bounded(rng: &mut utils::Rng, min: f64, max: f64) -> Self {
         Vec3(
-            utils::random_double_bounded(min, max),
-            utils::random_double_bounded(min, max),
-            utils::random_double_bounded(min, max),
+            utils::random_double_bounded(rng, min, max),
+            utils::random_double_bounded(rng, min, max),
+            utils::random_double_bounded(rng, min, max),
         )
     }
 
@@ -45,10 +48,19 @@ impl Vec3 {
         self.2
     }
 
+    pub fn axis(&self, n: usize) -> f64 {
+        match n {
+            1 => self.1,
+            2 => self.2,
+            _ => self.0,
+        }
+    }
+
     pub fn length(&self) -> f64 {
         self.length_squared().sqrt()
     }
 
+    #[allow(clippy::needless_return)]
     pub fn length_squared(&self) -> f64 {
         return self.0 * self.0 + self.1 * self.1 + self.2 * self.2;
     }
@@ -60,6 +72,12 @@ impl Vec3 {
     }
 }
 
+impl From<[f64; 3]> for Vec3 {
+    fn from(v: [f64; 3]) -> Self {
+        Vec3(v[0], v[1], v[2])
+    }
+}
+
 impl Mul for Vec3 {
     type Output = Vec3;
     fn mul(self, other: Vec3) -> Vec3 {
@@ -117,10 +135,12 @@ impl Neg for Vec3 {
     }
 }
 
+#[allow(clippy::needless_return)]
 pub fn dot(u: Vec3, v: Vec3) -> f64 {
     return u.x() * v.x() + u.y() * v.y() + u.z() * v.z();
 }
 
+#[allow(clippy::needless_return)]
 pub fn cross(u: Vec3, v: Vec3) -> Vec3 {
     return Vec3::new(
         u.y() * v.z() - u.z() * v.y(),
@@ -129,26 +149,29 @@ pub fn cross(u: Vec3, v: Vec3) -> Vec3 {
     );
 }
 
+#[allow(clippy::needless_return)]
 pub fn unit_vector(v: Vec3) -> Vec3 {
     return v / v.length();
 }
 
-pub fn random_in_unit_disk() -> Vec3 {
-    loop {
-        let p = Vec3::new(
-            utils::random_double_bounded(-1.0, 1.0),
-            utils::random_double_bounded(-1.0, 1.0),
-            0.0,
-        );
-        if p.length_squared() < 1.0 {
-            return p;
-        }
+pub fn sample_unit_disk((u1, u2): (f64, f64)) -> Vec3 {
+    // Maps a point of the unit square onto the unit disk with Shirley and Chiu's concentric
+    // mapping, which keeps stratified samples stratified.
+    let (a, b) = (2.0 * u1 - 1.0, 2.0 * u2 - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return Vec3::default();
     }
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, (utils::PI / 4.0) * (b / a))
+    } else {
+        (b, (utils::PI / 2.0) - (utils::PI / 4.0) * (a / b))
+    };
+    Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
 }
 
-pub fn random_unit_vector() -> Vec3 {
+pub fn random_unit_vector(rng: &mut utils::Rng) -> Vec3 {
     loop {
-        let p = Vec3::new_random_bounded(-1.0, 1.0);
+        let p = Vec3::new_random_bounded(rng, -1.0, 1.0);
         let lensq = p.length_squared();
         if 1e-160 < lensq && lensq <= 1.0 {
             return p / lensq.sqrt();
@@ -156,13 +179,32 @@ pub fn random_unit_vector() -> Vec3 {
     }
 }
 
-pub fn random_on_hemisphere(normal: Vec3) -> Vec3 {
-    let on_unit_sphere = random_unit_vector();
-    if dot(on_unit_sphere, normal) > 0.0 {
-        // in the same hemisphere as the normal
-        return on_unit_sphere;
-    }
-    return -on_unit_sphere;
+pub fn sample_cosine_direction((r1, r2): (f64, f64)) -> Vec3 {
+    // A direction about +z with density cos(theta) / pi.
+    let phi = 2.0 * utils::PI * r1;
+    let x = phi.cos() * r2.sqrt();
+    let y = phi.sin() * r2.sqrt();
+    let z = (1.0 - r2).sqrt();
+    Vec3::new(x, y, z)
+}
+
+pub fn sample_sphere((r1, r2): (f64, f64)) -> Vec3 {
+    // A direction uniform over the unit sphere.
+    let z = 1.0 - 2.0 * r2;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * utils::PI * r1;
+    Vec3::new(phi.cos() * r, phi.sin() * r, z)
+}
+
+pub fn sample_to_sphere((r1, r2): (f64, f64), radius: f64, distance_squared: f64) -> Vec3 {
+    // A direction about +z, uniform over the cone subtended by a sphere of the given radius
+    // whose center is distance_squared away.
+    let z = 1.0 + r2 * ((1.0 - radius * radius / distance_squared).max(0.0).sqrt() - 1.0);
+
+    let phi = 2.0 * utils::PI * r1;
+    let x = phi.cos() * (1.0 - z * z).sqrt();
+    let y = phi.sin() * (1.0 - z * z).sqrt();
+    Vec3::new(x, y, z)
 }
 
 pub fn reflect(v: Vec3, n: Vec3) -> Vec3 {